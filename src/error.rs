@@ -0,0 +1,173 @@
+//! Everything that can go wrong, and the exit status each one leaves with.
+//!
+//! | Status | Error                          | Meaning                                      |
+//! |--------|--------------------------------|----------------------------------------------|
+//! | 0      |                                | Success                                      |
+//! | 1      | `Usage`, `Pattern`, `Template` | Bad options, or an invalid pattern or format |
+//! | 2      | `Ambiguous`                    | More than one window matched                 |
+//! | 3      | `NoMatch`                      | Nothing matched                              |
+//! | 4      | `NoServer`                     | No tmux server is running                    |
+//! | 5      | `TmuxNotInstalled`             | There's no `tmux` binary on `$PATH`          |
+//! | 6      | `Parse`                        | tmux said something we didn't understand     |
+//! | 7      | `Tmux`, `Failed`               | A tmux command failed                        |
+//! | 8      | `Io`                           | Reading or writing something else failed     |
+//! | 9      | `File`, `NoProject`            | A saved snapshot or project file couldn't be |
+//! |        |                                | read                                         |
+//! | 130    | `Cancelled`                    | The user backed out of a choice              |
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use failure::Fail;
+
+use crate::format::ParseError;
+use crate::search::PatternError;
+use crate::template::TemplateError;
+use crate::tmux::Server;
+
+/// The kinds of file tinfo reads, for saying which one is wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Snapshot,
+    Project,
+}
+
+impl fmt::Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FileKind::Snapshot => write!(f, "snapshot"),
+            FileKind::Project => write!(f, "project"),
+        }
+    }
+}
+
+/// A problem with a line of a snapshot or project file.
+#[derive(Debug, PartialEq)]
+pub struct FileError {
+    pub kind: FileKind,
+    /// The line number the problem is on, counting from one.
+    pub line: usize,
+    pub message: String,
+}
+
+impl FileError {
+    pub fn new(kind: FileKind, line: usize, message: &str) -> FileError {
+        FileError { kind, line, message: message.to_string() }
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid {}, line {}: {}", self.kind, self.line, self.message)
+    }
+}
+
+impl Fail for FileError {}
+
+#[derive(Debug)]
+pub enum Error {
+    Usage(String),
+    Pattern(PatternError),
+    Template(TemplateError),
+    /// How many windows matched.
+    Ambiguous(usize),
+    NoMatch,
+    NoServer(Server),
+    TmuxNotInstalled,
+    Parse(ParseError),
+    /// A tmux command failed, with whatever it had to say about it.
+    Tmux(String),
+    /// Some of the commands run against many targets failed.
+    Failed { failed: usize, total: usize },
+    Io(io::Error),
+    File(FileError),
+    /// Where the project file was expected to be.
+    NoProject(PathBuf),
+    Cancelled,
+}
+
+impl Error {
+    /// The process exit status for this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Usage(_) | Error::Pattern(_) | Error::Template(_) => 1,
+            Error::Ambiguous(_) => 2,
+            Error::NoMatch => 3,
+            Error::NoServer(_) => 4,
+            Error::TmuxNotInstalled => 5,
+            Error::Parse(_) => 6,
+            Error::Tmux(_) | Error::Failed { .. } => 7,
+            Error::Io(_) => 8,
+            Error::File(_) | Error::NoProject(_) => 9,
+            Error::Cancelled => 130,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Usage(message) => write!(f, "{}", message),
+            Error::Pattern(e) => write!(f, "{}", e),
+            Error::Template(e) => write!(f, "{}", e),
+            Error::Ambiguous(n) => {
+                write!(f, "{} windows matched, narrow down the search to pick one", n)
+            }
+            Error::NoMatch => write!(f, "no windows matched"),
+            Error::NoServer(Server::Default) => write!(f, "no tmux server is running"),
+            Error::NoServer(server) => write!(f, "no tmux server is running on {}", server),
+            Error::TmuxNotInstalled => write!(f, "couldn't find tmux, is it installed?"),
+            Error::Parse(e) => write!(f, "couldn't understand tmux: {}", e),
+            Error::Tmux(message) => write!(f, "tmux: {}", message),
+            Error::Failed { failed, total } => write!(f, "{} of {} commands failed", failed, total),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::File(e) => write!(f, "{}", e),
+            Error::NoProject(path) => write!(f, "no project file at {}", path.display()),
+            Error::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl Fail for Error {
+    fn cause(&self) -> Option<&dyn Fail> {
+        match self {
+            Error::Pattern(e) => Some(e),
+            Error::Template(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::File(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Error {
+        Error::Parse(e)
+    }
+}
+
+impl From<PatternError> for Error {
+    fn from(e: PatternError) -> Error {
+        Error::Pattern(e)
+    }
+}
+
+impl From<TemplateError> for Error {
+    fn from(e: TemplateError) -> Error {
+        Error::Template(e)
+    }
+}
+
+impl From<FileError> for Error {
+    fn from(e: FileError) -> Error {
+        Error::File(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
@@ -0,0 +1,357 @@
+//! Structured parsing of tmux's `-F` output.
+//!
+//! Rather than scraping tmux's human readable listings, we ask for exactly
+//! the fields we want, joined by a separator that can't turn up inside any
+//! of them, and decode each line into a typed record.
+
+use std::fmt;
+use std::str::FromStr;
+
+use failure::Fail;
+
+use crate::{Flags, Pane, Session, Tab};
+
+/// Field separator. tmux escapes control characters in names, but not in
+/// paths, so each record puts its path last, where a separator inside it
+/// is taken as part of the path.
+pub const SEPARATOR: char = '\x1f';
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    FieldCount {
+        expected: usize,
+        found: usize,
+        line: String,
+    },
+    InvalidField {
+        field: &'static str,
+        value: String,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::FieldCount { expected, found, line } => write!(
+                f,
+                "expected {} fields but found {} in {:?}",
+                expected, found, line
+            ),
+            ParseError::InvalidField { field, value } => {
+                write!(f, "invalid {}: {:?}", field, value)
+            }
+        }
+    }
+}
+
+impl Fail for ParseError {}
+
+/// A row of output from one of tmux's `list-*` commands.
+pub trait Record: Sized {
+    /// The tmux format variables making up the record, in order.
+    const FIELDS: &'static [&'static str];
+
+    /// Whether the last field is a path, which tmux prints as it is,
+    /// newlines and all. A line short of fields then carries on the path
+    /// from the line before.
+    const PATH_LAST: bool = false;
+
+    fn decode(fields: &mut Fields) -> Result<Self, ParseError>;
+
+    /// The `-F` argument which asks tmux for this record.
+    fn format() -> String {
+        Self::FIELDS
+            .iter()
+            .map(|field| format!("#{{{}}}", field))
+            .collect::<Vec<_>>()
+            .join(&SEPARATOR.to_string())
+    }
+}
+
+/// The fields of a single line, consumed in order by `Record::decode`.
+pub struct Fields<'a> {
+    names: &'static [&'static str],
+    values: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn new(names: &'static [&'static str], line: &'a str) -> Result<Fields<'a>, ParseError> {
+        let values: Vec<&str> = line.splitn(names.len(), SEPARATOR).collect();
+        if values.len() != names.len() {
+            return Err(ParseError::FieldCount {
+                expected: names.len(),
+                found: values.len(),
+                line: line.to_string(),
+            });
+        }
+        Ok(Fields { names, values, pos: 0 })
+    }
+
+    pub fn text(&mut self) -> &'a str {
+        let value = self.values[self.pos];
+        self.pos += 1;
+        value
+    }
+
+    pub fn number<T: FromStr>(&mut self) -> Result<T, ParseError> {
+        let field = self.names[self.pos];
+        let value = self.text();
+        value.parse().map_err(|_| ParseError::InvalidField {
+            field,
+            value: value.to_string(),
+        })
+    }
+
+    pub fn flag(&mut self) -> Result<bool, ParseError> {
+        self.number::<usize>().map(|n| n > 0)
+    }
+
+    /// A number which tmux leaves empty when it doesn't have one.
+    pub fn optional_number<T: FromStr>(&mut self) -> Result<Option<T>, ParseError> {
+        if self.values[self.pos].is_empty() {
+            self.pos += 1;
+            return Ok(None);
+        }
+        self.number().map(Some)
+    }
+}
+
+/// Decode every line of a `list-*` command's output.
+pub fn parse<R: Record>(output: &str) -> Result<Vec<R>, ParseError> {
+    let mut lines: Vec<String> = vec![];
+    for line in output.lines() {
+        let short = line.split(SEPARATOR).count() < R::FIELDS.len();
+        match lines.last_mut() {
+            Some(last) if R::PATH_LAST && short => {
+                last.push('\n');
+                last.push_str(line);
+            }
+            _ if line.is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines.iter()
+        .map(|line| R::decode(&mut Fields::new(R::FIELDS, line)?))
+        .collect()
+}
+
+#[derive(Debug)]
+pub struct SessionRecord {
+    pub session: Session,
+    pub windows: usize,
+    pub attached: bool,
+    /// When the session was created, in seconds since the epoch.
+    pub created: u64,
+    /// When the session was last used, in seconds since the epoch.
+    pub activity: u64,
+    pub last_attached: Option<u64>,
+}
+
+impl Record for SessionRecord {
+    const FIELDS: &'static [&'static str] = &[
+        "session_id",
+        "session_name",
+        "session_windows",
+        "session_attached",
+        "session_created",
+        "session_activity",
+        "session_last_attached",
+    ];
+
+    fn decode(fields: &mut Fields) -> Result<SessionRecord, ParseError> {
+        let session = Session::new(fields.text(), fields.text());
+        Ok(SessionRecord {
+            session,
+            windows: fields.number()?,
+            attached: fields.flag()?,
+            created: fields.number()?,
+            activity: fields.number()?,
+            last_attached: fields.optional_number()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct WindowRecord {
+    pub session_id: String,
+    pub tab: Tab,
+}
+
+impl Record for WindowRecord {
+    const FIELDS: &'static [&'static str] = &[
+        "session_id",
+        "window_index",
+        "window_width",
+        "window_height",
+        "window_layout",
+        "window_activity",
+        "window_active",
+        "window_last_flag",
+        "window_zoomed_flag",
+        "window_bell_flag",
+        "window_silence_flag",
+        "window_name",
+    ];
+
+    fn decode(fields: &mut Fields) -> Result<WindowRecord, ParseError> {
+        let session_id = fields.text().to_string();
+        let number = fields.number()?;
+        let width = fields.number()?;
+        let height = fields.number()?;
+        let layout = fields.text().to_string();
+        let activity = fields.number()?;
+        let flags = Flags {
+            active: fields.flag()?,
+            last: fields.flag()?,
+            zoomed: fields.flag()?,
+            bell: fields.flag()?,
+            silence: fields.flag()?,
+        };
+        let mut tab = Tab::new(fields.text(), number);
+        tab.width = width;
+        tab.height = height;
+        tab.layout = layout;
+        tab.activity = activity;
+        tab.flags = flags;
+        Ok(WindowRecord { session_id, tab })
+    }
+}
+
+#[derive(Debug)]
+pub struct PaneRecord {
+    pub session_id: String,
+    pub window_index: usize,
+    pub pane: Pane,
+}
+
+impl Record for PaneRecord {
+    const FIELDS: &'static [&'static str] = &[
+        "session_id",
+        "window_index",
+        "pane_index",
+        "pane_id",
+        "pane_active",
+        "pane_pid",
+        "pane_width",
+        "pane_height",
+        "pane_current_command",
+        "pane_title",
+        "pane_current_path",
+    ];
+
+    const PATH_LAST: bool = true;
+
+    fn decode(fields: &mut Fields) -> Result<PaneRecord, ParseError> {
+        Ok(PaneRecord {
+            session_id: fields.text().to_string(),
+            window_index: fields.number()?,
+            pane: Pane {
+                index: fields.number()?,
+                id: fields.text().to_string(),
+                active: fields.flag()?,
+                pid: fields.number()?,
+                width: fields.number()?,
+                height: fields.number()?,
+                command: fields.text().to_string(),
+                title: fields.text().to_string(),
+                path: fields.text().to_string(),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            SessionRecord::format(),
+            concat!(
+                "#{session_id}\x1f#{session_name}\x1f#{session_windows}\x1f#{session_attached}\x1f",
+                "#{session_created}\x1f#{session_activity}\x1f#{session_last_attached}"
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let records: Vec<SessionRecord> = parse(
+            "$2\x1fwork\x1f3\x1f0\x1f1000\x1f2000\x1f\n$3\x1fapi\x1f1\x1f1\x1f1000\x1f2000\x1f1500\n"
+        ).unwrap();
+        assert_eq!(records[0].session.name, "work");
+        assert_eq!((records[0].created, records[0].activity), (1000, 2000));
+        assert_eq!(records[0].last_attached, None);
+        assert_eq!(records[1].last_attached, Some(1500));
+
+        let records: Vec<WindowRecord> = parse(
+            "$2\x1f4\x1f80\x1f24\x1fb25d,80x24,0,0,0\x1f1234\x1f1\x1f0\x1f1\x1f0\x1f0\x1fa: b (1 panes)\n"
+        ).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].session_id, "$2");
+        let tab = &records[0].tab;
+        assert_eq!(tab.name, "a: b (1 panes)");
+        assert_eq!(tab.number, 4);
+        assert_eq!((tab.width, tab.height), (80, 24));
+        assert_eq!(tab.layout, "b25d,80x24,0,0,0");
+        assert_eq!(tab.activity, 1234);
+        assert!(tab.flags.active && tab.flags.zoomed && !tab.flags.last);
+        assert_eq!(tab.flags.to_string(), "*Z");
+
+        let records: Vec<PaneRecord> = parse(
+            "$2\x1f4\x1f1\x1f%7\x1f1\x1f4242\x1f80\x1f24\x1fcargo\x1fbuild\x1f/src/api\n"
+        ).unwrap();
+        assert_eq!(records[0].window_index, 4);
+        let pane = &records[0].pane;
+        assert_eq!(pane.index, 1);
+        assert_eq!(pane.id, "%7");
+        assert!(pane.active);
+        assert_eq!(pane.pid, 4242);
+        assert_eq!((pane.width, pane.height), (80, 24));
+        assert_eq!(pane.command, "cargo");
+        assert_eq!(pane.path, "/src/api");
+        assert_eq!(pane.title, "build");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse::<WindowRecord>("$2\x1f4\n").unwrap_err(),
+            ParseError::FieldCount {
+                expected: 12,
+                found: 2,
+                line: "$2\x1f4".to_string(),
+            }
+        );
+        assert_eq!(
+            parse::<WindowRecord>("$2\x1ffour\x1f80\x1f24\x1f\x1f0\x1f0\x1f0\x1f0\x1f0\x1f0\x1fzsh\n").unwrap_err(),
+            ParseError::InvalidField {
+                field: "window_index",
+                value: "four".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_awkward_paths() {
+        let records: Vec<PaneRecord> = parse(
+            "$2\x1f4\x1f1\x1f%7\x1f1\x1f4242\x1f80\x1f24\x1fzsh\x1fzsh\x1f/tmp/a\x1fb\n"
+        ).unwrap();
+        assert_eq!(records[0].pane.path, "/tmp/a\x1fb");
+
+        let records: Vec<PaneRecord> = parse(concat!(
+            "$2\x1f4\x1f0\x1f%6\x1f1\x1f4241\x1f80\x1f24\x1fzsh\x1fzsh\x1f/tmp/a\n",
+            "b\n",
+            "$2\x1f4\x1f1\x1f%7\x1f1\x1f4242\x1f80\x1f24\x1fzsh\x1fzsh\x1f/src\n",
+            "\n",
+        )).unwrap();
+        let paths: Vec<_> = records.iter().map(|record| record.pane.path.as_str()).collect();
+        assert_eq!(paths, vec!["/tmp/a\nb", "/src\n"]);
+
+        // Anything else short of fields is still an error.
+        assert!(parse::<PaneRecord>("b\n$2\x1f4\x1f1\x1f%7\x1f1\x1f4242\x1f80\x1f24\x1fzsh\x1fzsh\x1f/src\n").is_err());
+        assert!(parse::<WindowRecord>("$2\x1f4\x1f80\x1f24\x1f\x1f0\x1f0\x1f0\x1f0\x1f0\x1f0\x1fzsh\nvim\n").is_err());
+    }
+}
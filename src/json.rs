@@ -0,0 +1,241 @@
+//! JSON output.
+//!
+//! The schema is versioned by `SCHEMA_VERSION`, which is bumped whenever a
+//! field is removed or changes meaning; new fields may turn up at any time.
+//!
+//! `--json` writes a single document:
+//!
+//! ```json
+//! {"version": 1, "sessions": [SESSION, ...]}
+//! ```
+//!
+//! where each `SESSION` is
+//!
+//! ```json
+//! {"server": "default", "id": "$0", "name": "work", "attached": true,
+//!  "created": 1700000000, "activity": 1700000300, "last_attached": null,
+//!  "windows": [WINDOW, ...]}
+//! ```
+//!
+//! each `WINDOW` is
+//!
+//! ```json
+//! {"index": 0, "name": "zsh", "width": 80, "height": 24,
+//!  "layout": "b25d,80x24,0,0,0", "activity": 1700000300, "active": true,
+//!  "last": false, "zoomed": false, "bell": false, "silence": false,
+//!  "panes": [PANE, ...]}
+//! ```
+//!
+//! and each `PANE` is
+//!
+//! ```json
+//! {"index": 0, "id": "%0", "active": true, "pid": 4242, "width": 80,
+//!  "height": 24, "command": "zsh", "path": "/home/me", "title": "zsh"}
+//! ```
+//!
+//! `--json-lines` writes one object per window instead, with its session
+//! alongside it minus the `windows`:
+//!
+//! ```json
+//! {"version": 1, "session": SESSION, "window": WINDOW}
+//! ```
+//!
+//! Times are in seconds since the epoch, or null if they never happened.
+//! Sessions come out in the order they were sorted in.
+
+use std::io::{self, Write};
+
+use crate::{Pane, Session, Tab, Window};
+
+pub const SCHEMA_VERSION: usize = 1;
+
+/// Something which can be written out as JSON.
+pub trait ToJson {
+    fn to_json(&self) -> String;
+}
+
+impl ToJson for str {
+    fn to_json(&self) -> String {
+        let mut out = String::with_capacity(self.len() + 2);
+        out.push('"');
+        for c in self.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+        out
+    }
+}
+
+impl ToJson for String {
+    fn to_json(&self) -> String {
+        self.as_str().to_json()
+    }
+}
+
+impl ToJson for bool {
+    fn to_json(&self) -> String {
+        self.to_string()
+    }
+}
+
+macro_rules! number_to_json {
+    ($($t:ty),*) => {
+        $(impl ToJson for $t {
+            fn to_json(&self) -> String {
+                self.to_string()
+            }
+        })*
+    };
+}
+
+number_to_json!(usize, u32, u64, i64);
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json(&self) -> String {
+        match self {
+            Some(value) => value.to_json(),
+            None => "null".to_string(),
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for [T] {
+    fn to_json(&self) -> String {
+        let items: Vec<_> = self.iter().map(ToJson::to_json).collect();
+        format!("[{}]", items.join(","))
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> String {
+        self.as_slice().to_json()
+    }
+}
+
+/// A JSON object, with its keys in the order they were added.
+#[derive(Debug, Default)]
+pub struct Object(Vec<(&'static str, String)>);
+
+impl Object {
+    pub fn new() -> Object {
+        Object::default()
+    }
+
+    pub fn field<T: ToJson + ?Sized>(mut self, key: &'static str, value: &T) -> Object {
+        self.0.push((key, value.to_json()));
+        self
+    }
+}
+
+impl ToJson for Object {
+    fn to_json(&self) -> String {
+        let fields: Vec<_> = self.0.iter()
+            .map(|(key, value)| format!("{}:{}", key.to_json(), value))
+            .collect();
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+impl ToJson for Pane {
+    fn to_json(&self) -> String {
+        Object::new()
+            .field("index", &self.index)
+            .field("id", &self.id)
+            .field("active", &self.active)
+            .field("pid", &self.pid)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("command", &self.command)
+            .field("path", &self.path)
+            .field("title", &self.title)
+            .to_json()
+    }
+}
+
+impl ToJson for Tab {
+    fn to_json(&self) -> String {
+        Object::new()
+            .field("index", &self.number)
+            .field("name", &self.name)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("layout", &self.layout)
+            .field("activity", &self.activity)
+            .field("active", &self.flags.active)
+            .field("last", &self.flags.last)
+            .field("zoomed", &self.flags.zoomed)
+            .field("bell", &self.flags.bell)
+            .field("silence", &self.flags.silence)
+            .field("panes", &self.panes)
+            .to_json()
+    }
+}
+
+fn session(session: &Session, window: &Window) -> Object {
+    Object::new()
+        .field("server", &session.server.to_string())
+        .field("id", &session.id)
+        .field("name", &session.name)
+        .field("attached", &window.attached)
+        .field("created", &window.created)
+        .field("activity", &window.activity)
+        .field("last_attached", &window.last_attached)
+}
+
+/// Write `sessions` as a single JSON document.
+pub fn dump<W: Write>(sessions: &[(&Session, &Window)], w: &mut W) -> io::Result<()> {
+    let sessions: Vec<_> = sessions.iter()
+        .map(|(s, window)| session(s, window).field("windows", &window.tabs))
+        .collect();
+    let document = Object::new()
+        .field("version", &SCHEMA_VERSION)
+        .field("sessions", &sessions);
+    writeln!(w, "{}", document.to_json())
+}
+
+/// Write `sessions` as one JSON object per window.
+pub fn dump_lines<W: Write>(sessions: &[(&Session, &Window)], w: &mut W) -> io::Result<()> {
+    for (s, window) in sessions.iter() {
+        for tab in window.tabs.iter() {
+            let line = Object::new()
+                .field("version", &SCHEMA_VERSION)
+                .field("session", &session(s, window))
+                .field("window", tab);
+            writeln!(w, "{}", line.to_json())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!("plain".to_json(), r#""plain""#);
+        assert_eq!("a \"b\" \\ c\n".to_json(), r#""a \"b\" \\ c\n""#);
+        assert_eq!("\x1f ü".to_json(), r#""\u001f ü""#);
+    }
+
+    #[test]
+    fn test_object() {
+        let object = Object::new()
+            .field("name", "zsh")
+            .field("index", &3usize)
+            .field("missing", &None::<bool>)
+            .field("list", &vec![true, false]);
+        assert_eq!(
+            object.to_json(),
+            r#"{"name":"zsh","index":3,"missing":null,"list":[true,false]}"#
+        );
+    }
+}
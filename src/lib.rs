@@ -0,0 +1,1603 @@
+
+//! A frontend to manipulating tmux.
+//!
+//! tinfo models a tmux server as a `WindowList`: every session, and the
+//! windows ("tabs") inside it. Build one with `build_windowlist`, narrow it
+//! down with `WindowSearch::select_tabs`, then act on what's left.
+//!
+//! ```no_run
+//! use tinfo::{build_windowlist, Get, Search, WindowSearch};
+//! use tinfo::tmux::{ProcessBackend, Server};
+//!
+//! let tmux = ProcessBackend;
+//! let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+//! windows.select_tabs(&Search::name("vim")).get_cmd(&tmux, &Get::default()).unwrap();
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::io::{self, Write};
+
+pub mod error;
+pub mod format;
+pub mod json;
+pub mod picker;
+pub mod plan;
+pub mod project;
+pub mod search;
+pub mod snapshot;
+pub mod sort;
+pub mod template;
+#[cfg(any(test, feature = "test-support"))]
+pub mod testing;
+pub mod tmux;
+
+pub use error::Error;
+pub use search::Search;
+
+use format::{PaneRecord, Record, SessionRecord, WindowRecord};
+use plan::Step;
+use sort::Sort;
+use template::{Row, Template};
+use tmux::{Server, TmuxBackend};
+
+/// A tmux session, identified by both its name and its `$id`.
+///
+/// The name is what humans see; the id is what we hand back to tmux as a
+/// target, since names may contain spaces, unicode or anything else. Ids are
+/// only unique within a server, so the server is part of the identity too.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Session {
+    /// tmux's `$id` for the session.
+    pub id: String,
+    pub name: String,
+    /// The server the session lives on.
+    pub server: Server,
+}
+
+impl Session {
+    pub fn new(id: &str, name: &str) -> Session {
+        Session {
+            id: id.to_string(),
+            name: name.to_string(),
+            server: Server::Default,
+        }
+    }
+
+    /// A tmux target for this session.
+    pub fn target(&self) -> String {
+        self.id.clone()
+    }
+
+    /// A tmux target for one of this session's windows.
+    pub fn window_target(&self, tab: &Tab) -> String {
+        format!("{}:{}", self.id, tab.number)
+    }
+
+    /// A tmux target for one of this session's panes.
+    pub fn pane_target(&self, tab: &Tab, pane: &Pane) -> String {
+        format!("{}:{}.{}", self.id, tab.number, pane.index)
+    }
+
+    /// The most specific tmux target for what matched in this session: the
+    /// pane if only one did, otherwise the window if only one did, otherwise
+    /// the session as a whole.
+    pub fn focus_target(&self, window: &Window) -> String {
+        match window.tabs.as_slice() {
+            [tab] => match tab.panes.as_slice() {
+                [pane] => self.pane_target(tab, pane),
+                _ => self.window_target(tab),
+            },
+            _ => self.target(),
+        }
+    }
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// A single pane within a tmux window.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pane {
+    /// The pane's index within its window.
+    pub index: usize,
+    /// tmux's `%id` for the pane.
+    pub id: String,
+    /// Whether this is the window's active pane.
+    pub active: bool,
+    /// The pid of the pane's initial process.
+    pub pid: u32,
+    pub width: usize,
+    pub height: usize,
+    /// The command currently running in the foreground.
+    pub command: String,
+    /// The current working directory.
+    pub path: String,
+    pub title: String,
+}
+
+/// The state tmux flags a window with in its status line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Flags {
+    /// This is the session's current window.
+    pub active: bool,
+    /// This was the session's previous window.
+    pub last: bool,
+    /// One of the window's panes is zoomed.
+    pub zoomed: bool,
+    /// A bell has rung in the window.
+    pub bell: bool,
+    /// The window has fallen silent.
+    pub silence: bool,
+}
+
+/// The flags as tmux shows them, as in `#{window_flags}`.
+impl fmt::Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbols = [
+            (self.active, '*'),
+            (self.last, '-'),
+            (self.bell, '!'),
+            (self.silence, '~'),
+            (self.zoomed, 'Z'),
+        ];
+        for (set, symbol) in symbols.iter() {
+            if *set {
+                write!(f, "{}", symbol)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A tmux window.
+#[derive(Debug, Clone)]
+pub struct Tab {
+    pub name: String,
+    /// The window's index within its session.
+    pub number: usize,
+    pub width: usize,
+    pub height: usize,
+    /// The window's layout, in tmux's own notation for `select-layout`.
+    pub layout: String,
+    /// When the window last saw any activity, in seconds since the epoch.
+    pub activity: u64,
+    pub flags: Flags,
+    pub panes: Vec<Pane>,
+}
+
+impl Tab {
+    pub fn new(name: &str, number: usize) -> Tab {
+        Tab {
+            name: name.to_string(),
+            number,
+            width: 0,
+            height: 0,
+            layout: String::new(),
+            activity: 0,
+            flags: Flags::default(),
+            panes: vec![],
+        }
+    }
+}
+
+/// The windows of a single tmux session.
+#[derive(Debug)]
+pub struct Window {
+    pub tabs: Vec<Tab>,
+    /// Whether any client is attached to the session.
+    pub attached: bool,
+    /// When the session was created, in seconds since the epoch.
+    pub created: u64,
+    /// When the session was last used, in seconds since the epoch.
+    pub activity: u64,
+    /// When a client last attached to the session, if one ever has.
+    pub last_attached: Option<u64>,
+}
+
+impl Window {
+    pub fn new(tabs: Vec<Tab>, attached: bool) -> Window {
+        Window {
+            tabs,
+            attached,
+            created: 0,
+            activity: 0,
+            last_attached: None,
+        }
+    }
+
+    /// A copy of this session's details, without any of its windows.
+    pub fn without_tabs(&self) -> Window {
+        Window {
+            tabs: vec![],
+            ..*self
+        }
+    }
+
+    pub fn push(&mut self, tab: Tab) {
+        self.tabs.push(tab);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tabs.is_empty()
+    }
+}
+
+/// Every session on a server, with its windows.
+pub type WindowList = HashMap<Session, Window>;
+
+/// A single window picked out by a search, and how well it matched.
+#[derive(Debug, Clone)]
+pub struct Match<'a> {
+    pub session: &'a Session,
+    pub window: &'a Window,
+    pub tab: &'a Tab,
+    /// The panes of the window which matched.
+    pub panes: Vec<&'a Pane>,
+    pub score: i64,
+}
+
+/// How far ahead of the runner-up the best match must be before we'll act
+/// on it alone, as a fraction of the runner-up's score.
+const CLEAR_LEAD: (i64, i64) = (3, 2);
+
+/// The best of some ranked matches, if it clearly beats the rest.
+///
+/// A lone match always wins. Otherwise the winner needs to outscore the
+/// runner-up by half again, which only fuzzy matching can manage since
+/// every other mode scores everything equally.
+pub fn clear_winner<'a, 'b>(matches: &'b [Match<'a>]) -> Option<&'b Match<'a>> {
+    match matches {
+        [only] => Some(only),
+        [best, runner_up, ..] => {
+            let (num, den) = CLEAR_LEAD;
+            let clear = best.score > runner_up.score
+                && best.score * den >= runner_up.score * num;
+            if clear {
+                Some(best)
+            } else {
+                None
+            }
+        }
+        [] => None,
+    }
+}
+
+/// Gather matches back up into a `WindowList`, ready to act on.
+pub fn collect_matches(matches: &[Match]) -> WindowList {
+    let mut out: WindowList = HashMap::new();
+    for m in matches {
+        out.entry(m.session.clone())
+            .or_insert_with(|| m.window.without_tabs())
+            .push(Tab {
+                panes: m.panes.iter().map(|&pane| pane.clone()).collect(),
+                ..m.tab.clone()
+            });
+    }
+    for window in out.values_mut() {
+        window.tabs.sort_by_key(|tab| tab.number);
+    }
+    out
+}
+
+impl<'a> fmt::Display for Match<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.session, self.tab.number, self.tab.name)
+    }
+}
+
+/// A line describing each match, in order, as `session:window: name`.
+/// When the matches come from more than one server, each is prefixed with
+/// the server it's on, as `[server] session:window: name`, since sessions
+/// on different servers may well share names.
+pub fn match_labels(matches: &[Match]) -> Vec<String> {
+    let servers = matches.iter().map(|m| &m.session.server).collect::<HashSet<_>>();
+    matches.iter()
+        .map(|m| if servers.len() > 1 {
+            format!("[{}] {}", m.session.server, m)
+        } else {
+            m.to_string()
+        })
+        .collect()
+}
+
+/// Write matches one per line, in order, as `match_labels` describes them.
+pub fn dump_matches<W: Write>(w: &mut W, matches: &[Match]) -> io::Result<()> {
+    for label in match_labels(matches) {
+        writeln!(w, "{}", label)?;
+    }
+    Ok(())
+}
+
+pub trait WindowSearch {
+    /// The windows matching `search`, grouped by session.
+    fn select_tabs(&self, search: &Search) -> Self;
+    /// The windows matching `search`, best first.
+    fn ranked<'a>(&'a self, search: &Search) -> Vec<Match<'a>>;
+    /// Fill in the windows of every session on `server` from tmux.
+    fn populate(&mut self, tmux: &dyn TmuxBackend, server: &Server) -> Result<(), Error>;
+    /// Sessions in the order given by `sort`, ready to list.
+    fn sorted(&self, sort: &Sort) -> Listing<'_>;
+    /// Write a human readable listing, sorted by name.
+    fn dump<W: Write>(&self, w: &mut W) -> io::Result<()>;
+    /// Write a human readable listing, including every window's panes.
+    fn dump_panes<W: Write>(&self, w: &mut W) -> io::Result<()>;
+    /// Write a line per window, or per pane if `panes` is set, expanding
+    /// `template` for each.
+    fn dump_format<W: Write>(&self, w: &mut W, template: &Template, panes: bool) -> io::Result<()>;
+    /// Write a JSON document, as described in the `json` module.
+    fn dump_json<W: Write>(&self, w: &mut W) -> io::Result<()>;
+    /// Write a JSON object per window, as described in the `json` module.
+    fn dump_json_lines<W: Write>(&self, w: &mut W) -> io::Result<()>;
+    /// Bring the single matched window into the current session, as `get`
+    /// describes.
+    fn get_cmd(&self, tmux: &dyn TmuxBackend, get: &Get) -> Result<(), Error>;
+    /// Take the user to the single matched session, switching to it if
+    /// they're already inside tmux on the same server and attaching to it
+    /// otherwise. The matched window and pane are selected on the way.
+    fn attach_cmd(&self, tmux: &dyn TmuxBackend, attach: &Attach) -> Result<(), Error>;
+    /// Switch the current client to the single matched session, selecting
+    /// the matched window and pane.
+    fn switch_cmd(&self, tmux: &dyn TmuxBackend) -> Result<(), Error>;
+    /// Go straight to the single matched window, wherever it is, and to its
+    /// pane if only one matched: select them, then switch or attach to their
+    /// session as `attach_cmd` would.
+    fn go_cmd(&self, tmux: &dyn TmuxBackend, attach: &Attach) -> Result<(), Error>;
+    /// Rename the single matched window.
+    fn rename_cmd(&self, tmux: &dyn TmuxBackend, name: &str) -> Result<(), Error>;
+    /// Rename the single matched session.
+    fn rename_session_cmd(&self, tmux: &dyn TmuxBackend, name: &str) -> Result<(), Error>;
+    /// Kill every matched window.
+    fn kill_cmd(&self, tmux: &dyn TmuxBackend) -> Result<(), Error>;
+    /// Kill every session with a matched window in it.
+    fn kill_session_cmd(&self, tmux: &dyn TmuxBackend) -> Result<(), Error>;
+    /// Press `keys` in the single matched window.
+    fn send_cmd(&self, tmux: &dyn TmuxBackend, keys: &Keys) -> Result<(), Error>;
+    /// The commands which would do `action`, ready to show or run: to the
+    /// single matched window or session, or to every match when killing.
+    fn plan(&self, action: &Action) -> Result<Vec<Step>, Error>;
+    /// The commands which would do `action` to every match at once. Only
+    /// getting, killing and sending keys can be done to more than one, and
+    /// windows got all at once can't share an index.
+    fn plan_all(&self, action: &Action) -> Result<Vec<Step>, Error>;
+}
+
+/// How `get_cmd` brings a window into the current session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GetMode {
+    /// Move it, taking it out of its own session.
+    #[default]
+    Move,
+    /// Link it, so that it's in both sessions at once.
+    Link,
+    /// Exchange it with the current window.
+    Swap,
+    /// Unlink it from the session it matched in, leaving it in whichever
+    /// others it's linked into.
+    Unlink,
+}
+
+impl GetMode {
+    fn command(self) -> &'static str {
+        match self {
+            GetMode::Move => "move-window",
+            GetMode::Link => "link-window",
+            GetMode::Swap => "swap-window",
+            GetMode::Unlink => "unlink-window",
+        }
+    }
+}
+
+/// Everything about how `get_cmd` brings a window here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Get {
+    pub mode: GetMode,
+    /// The index for the window in the current session, or for swapping
+    /// the window at; the next free one, or the current window, if not.
+    pub index: Option<usize>,
+    /// The name of the session to bring the window into, if not the
+    /// current one.
+    pub into: Option<String>,
+    /// Whether to select the window once it's here.
+    pub focus: bool,
+}
+
+impl Default for Get {
+    fn default() -> Get {
+        Get {
+            mode: GetMode::Move,
+            index: None,
+            into: None,
+            focus: true,
+        }
+    }
+}
+
+/// Keys for `send_cmd` to press, as `send-keys` takes them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keys {
+    pub keys: Vec<String>,
+    /// Whether to press them in every pane of the window, rather than just
+    /// its active one.
+    pub each_pane: bool,
+}
+
+/// Something to do with what a search matched, which `WindowSearch::plan`
+/// turns into tmux commands. Each is what the `_cmd` method of the same
+/// name does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Get(Get),
+    Attach(Attach),
+    Switch,
+    Go(Attach),
+    Rename(String),
+    RenameSession(String),
+    Kill,
+    KillSession,
+    Send(Keys),
+}
+
+/// Where the user is attaching from.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Attach {
+    /// The server whose client we're running in, if we're inside tmux.
+    pub inside: Option<Server>,
+    /// When attaching a new client, detach any others from the session.
+    pub detach_others: bool,
+}
+
+impl Attach {
+    /// Work out from `$TMUX` whether we're inside tmux.
+    pub fn from_env(detach_others: bool) -> Attach {
+        Attach {
+            inside: env::var_os("TMUX").map(|_| Server::from_env()),
+            detach_others,
+        }
+    }
+
+    /// Whether there's already a client on `server` to switch, rather than
+    /// attaching a new one.
+    pub fn is_inside(&self, server: &Server) -> bool {
+        match self.inside {
+            Some(ref inside) => inside.is_same(server),
+            None => false,
+        }
+    }
+}
+
+/// Run a tmux command which prints records, such as `list-*`, and decode
+/// its output.
+fn list<R: Record>(tmux: &dyn TmuxBackend, server: &Server, args: &[&str]) -> Result<Vec<R>, Error> {
+    let format = R::format();
+    let mut args = args.to_vec();
+    args.extend(&["-F", &format]);
+    Ok(format::parse(&tmux.output(server, &args)?)?)
+}
+
+/// Run a tmux command which creates a pane, such as `new-window -P`,
+/// returning what it made. The format goes straight after the command
+/// name, since a shell command to run may follow the options.
+fn create(tmux: &dyn TmuxBackend, server: &Server, args: &[&str]) -> Result<PaneRecord, Error> {
+    let format = PaneRecord::format();
+    let mut args = args.to_vec();
+    args.splice(1..1, ["-F", &format]);
+    let mut created: Vec<PaneRecord> = format::parse(&tmux.output(server, &args)?)?;
+    created.pop().ok_or_else(|| Error::Tmux(format!("{} didn't say what it made", args[0])))
+}
+
+/// Expand a leading `~` to `$HOME`, as the shell would have.
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix('~'), env::var("HOME")) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", home, rest)
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// Type `command` into a pane, as though the user had, and run it.
+fn type_command(tmux: &dyn TmuxBackend, server: &Server, pane: &str, command: &str) -> Result<(), Error> {
+    tmux.run(server, &["send-keys", "-t", pane, "-l", command, ";", "send-keys", "-t", pane, "Enter"])
+}
+
+/// Query a tmux server for every session and window it knows about.
+pub fn build_windowlist(tmux: &dyn TmuxBackend, server: &Server) -> Result<WindowList, Error> {
+    let mut windows: WindowList = HashMap::new();
+
+    for mut record in list::<SessionRecord>(tmux, server, &["list-sessions"])? {
+        record.session.server = server.clone();
+        let vec = Vec::with_capacity(record.windows);
+        let mut window = Window::new(vec, record.attached);
+        window.created = record.created;
+        window.activity = record.activity;
+        window.last_attached = record.last_attached;
+        windows.insert(record.session, window);
+    }
+
+    // A server kept alive without sessions has no current target, which
+    // fails `list-windows -a`.
+    if !windows.is_empty() {
+        windows.populate(tmux, server)?;
+    }
+    for window in windows.values_mut() {
+        window.tabs.sort_by_key(|tab| tab.number);
+    }
+
+    Ok(windows)
+}
+
+/// Start a new session called `name` on `server`, in `dir` and running
+/// `command` if given, and return it ready to attach to.
+pub fn new_session(
+    tmux: &dyn TmuxBackend,
+    server: &Server,
+    name: &str,
+    dir: Option<&str>,
+    command: Option<&str>,
+) -> Result<WindowList, Error> {
+    let mut args = vec!["new-session", "-d", "-P", "-s", name];
+    if let Some(dir) = dir {
+        args.extend(&["-c", dir]);
+    }
+    args.extend(command);
+    let created = create(tmux, server, &args)?;
+
+    let session: WindowList = build_windowlist(tmux, server)?
+        .into_iter()
+        .filter(|(session, _)| session.id == created.session_id)
+        .collect();
+    if session.is_empty() {
+        return Err(Error::NoMatch);
+    }
+    Ok(session)
+}
+
+/// Query several tmux servers at once, combining their sessions into a
+/// single list. Sockets left behind by servers which have since gone away
+/// are skipped, but if none of them answers there is no server.
+pub fn build_serverlist(tmux: &dyn TmuxBackend, servers: &[Server]) -> Result<WindowList, Error> {
+    let mut windows: WindowList = HashMap::new();
+    let mut answered = false;
+    for server in servers {
+        match build_windowlist(tmux, server) {
+            Ok(found) => {
+                answered = true;
+                windows.extend(found);
+            }
+            Err(Error::NoServer(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    if !answered {
+        return Err(Error::NoServer(Server::Default));
+    }
+    Ok(windows)
+}
+
+fn count_tabs(windows: &WindowList) -> usize {
+    windows.values().map(|window| window.tabs.len()).sum()
+}
+
+/// Pull the single matched session out of a search result.
+fn single_match(windows: &WindowList) -> Result<(&Session, &Window), Error> {
+    let mut iter = windows.iter();
+    match (iter.next(), iter.next()) {
+        (Some(found), None) => Ok(found),
+        (None, _) => Err(Error::NoMatch),
+        _ => Err(Error::Ambiguous(count_tabs(windows))),
+    }
+}
+
+/// Pull the single matched window out of a search result.
+fn single_tab(windows: &WindowList) -> Result<(&Session, &Tab), Error> {
+    let (session, window) = single_match(windows)?;
+    match window.tabs.as_slice() {
+        [tab] => Ok((session, tab)),
+        tabs => Err(Error::Ambiguous(tabs.len())),
+    }
+}
+
+/// How a window is described to the user, as it is in a list of matches.
+fn window_label(session: &Session, tab: &Tab) -> String {
+    format!("{}:{}: {}", session, tab.number, tab.name)
+}
+
+fn get_step(session: &Session, tab: &Tab, get: &Get) -> Step {
+    let label = window_label(session, tab);
+    let source = session.window_target(tab);
+
+    let mut args = vec![get.mode.command()];
+    if get.mode == GetMode::Unlink {
+        args.extend(&["-t", &source]);
+        return Step::new(&label, &session.server, &args);
+    }
+    if !get.focus {
+        args.push("-d");
+    }
+    args.extend(&["-s", &source]);
+    let index = get.index.map(|index| index.to_string()).unwrap_or_default();
+    let destination = match get.into {
+        Some(ref into) => Some(format!("={}:{}", into, index)),
+        None if get.index.is_some() => Some(format!(":{}", index)),
+        None => None,
+    };
+    if let Some(ref destination) = destination {
+        args.extend(&["-t", destination]);
+    }
+    Step::new(&label, &session.server, &args)
+}
+
+fn send_steps(session: &Session, tab: &Tab, keys: &Keys) -> Vec<Step> {
+    let targets: Vec<(String, String)> = if keys.each_pane {
+        tab.panes.iter()
+            .map(|pane| {
+                let label = format!("{}:{}.{}: {}", session, tab.number, pane.index, tab.name);
+                (label, session.pane_target(tab, pane))
+            })
+            .collect()
+    } else {
+        vec![(window_label(session, tab), session.window_target(tab))]
+    };
+    targets.iter()
+        .map(|(label, target)| {
+            let mut args = vec!["send-keys", "-t", target];
+            args.extend(keys.keys.iter().map(String::as_str));
+            Step::new(label, &session.server, &args)
+        })
+        .collect()
+}
+
+/// The command which takes the user to `target`, given where they are.
+fn enter_args<'a>(target: &'a str, attach: &Attach, server: &Server) -> Vec<&'a str> {
+    if attach.is_inside(server) {
+        return vec!["switch-client", "-t", target];
+    }
+    let mut args = vec!["attach-session"];
+    if attach.detach_others {
+        args.push("-d");
+    }
+    args.extend(&["-t", target]);
+    args
+}
+
+/// A step running the commands built by `enter_args`. Switching a client
+/// is over as soon as tmux is done, but attaching one takes over the
+/// terminal for as long as it lasts, so tmux replaces us instead.
+fn enter_step(label: &str, server: &Server, args: &[&str], attach: &Attach) -> Step {
+    if attach.is_inside(server) {
+        Step::new(label, server, args)
+    } else {
+        Step::exec(label, server, args)
+    }
+}
+
+/// The templates making up the human readable listing. Listing panes adds
+/// more detail to sessions and windows too.
+const SESSION_TEMPLATE: &str = "Session: {session}{?attached: (attached)}";
+const SESSION_DETAIL_TEMPLATE: &str = "Session: {session}{?attached: (attached)}, \
+    created {session.created:time}\
+    {?session.last_attached:, last attached {session.last_attached:time}}";
+const WINDOW_TEMPLATE: &str = "  {window.index}: {window.name}{window.flags} [{width}x{height}]";
+const WINDOW_DETAIL_TEMPLATE: &str = "  {window.index}: {window.name}{window.flags} [{width}x{height}] \
+    [layout {window.layout}], active {window.activity:time}";
+const PANE_TEMPLATE: &str =
+    "    {pane.index}: {pane.command} {pane.path} [{pane.width}x{pane.height}]{?pane.active: (active)}";
+
+fn dump_sessions<'a, W, I>(w: &mut W, sessions: I, indent: &str, panes: bool) -> io::Result<()>
+where
+    W: Write,
+    I: Iterator<Item = (&'a Session, &'a Window)>,
+{
+    let parse = |source| Template::parse(source).expect("built in templates are valid");
+    let (session_template, window_template) = if panes {
+        (parse(SESSION_DETAIL_TEMPLATE), parse(WINDOW_DETAIL_TEMPLATE))
+    } else {
+        (parse(SESSION_TEMPLATE), parse(WINDOW_TEMPLATE))
+    };
+    let pane_template = parse(PANE_TEMPLATE);
+
+    for (session, window) in sessions {
+        let mut row = Row { session, window, tab: None, pane: None };
+        writeln!(w, "{}{}", indent, session_template.render(&row))?;
+        for tab in window.tabs.iter() {
+            row.tab = Some(tab);
+            row.pane = None;
+            writeln!(w, "{}{}", indent, window_template.render(&row))?;
+            if !panes {
+                continue;
+            }
+            for pane in tab.panes.iter() {
+                row.pane = Some(pane);
+                writeln!(w, "{}{}", indent, pane_template.render(&row))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sessions, in the order they're to be listed.
+pub struct Listing<'a>(Vec<(&'a Session, &'a Window)>);
+
+impl<'a> Listing<'a> {
+    pub fn new(windows: &'a WindowList, sort: &Sort) -> Listing<'a> {
+        let mut sessions: Vec<_> = windows.iter().collect();
+        sessions.sort_by(|a, b| sort.compare(*a, *b));
+        Listing(sessions)
+    }
+
+    /// Write a human readable listing, including every window's panes if
+    /// `panes` is set.
+    pub fn dump<W: Write>(&self, w: &mut W, panes: bool) -> io::Result<()> {
+        let mut servers: Vec<&Server> = vec![];
+        for (session, _) in self.0.iter() {
+            if !servers.contains(&&session.server) {
+                servers.push(&session.server);
+            }
+        }
+
+        // Only bother showing servers when there's more than one of them.
+        if servers.len() <= 1 {
+            return dump_sessions(w, self.0.iter().cloned(), "", panes);
+        }
+        for server in servers {
+            writeln!(w, "Server: {}", server)?;
+            let sessions = self.0.iter().cloned().filter(|(session, _)| &session.server == server);
+            dump_sessions(w, sessions, "  ", panes)?;
+        }
+        Ok(())
+    }
+
+    /// Write a line per window, or per pane if `panes` is set, expanding
+    /// `template` for each.
+    pub fn dump_format<W: Write>(&self, w: &mut W, template: &Template, panes: bool) -> io::Result<()> {
+        for &(session, window) in self.0.iter() {
+            for tab in window.tabs.iter() {
+                let row = Row { session, window, tab: Some(tab), pane: None };
+                if !panes {
+                    writeln!(w, "{}", template.render(&row))?;
+                    continue;
+                }
+                for pane in tab.panes.iter() {
+                    let row = Row { pane: Some(pane), ..row };
+                    writeln!(w, "{}", template.render(&row))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a JSON document, as described in the `json` module.
+    pub fn dump_json<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        json::dump(&self.0, w)
+    }
+
+    /// Write a JSON object per window, as described in the `json` module.
+    pub fn dump_json_lines<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        json::dump_lines(&self.0, w)
+    }
+}
+
+impl WindowSearch for WindowList {
+    fn sorted(&self, sort: &Sort) -> Listing<'_> {
+        Listing::new(self, sort)
+    }
+
+    fn dump<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.sorted(&Sort::default()).dump(w, false)
+    }
+
+    fn dump_panes<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.sorted(&Sort::default()).dump(w, true)
+    }
+
+    fn dump_format<W: Write>(&self, w: &mut W, template: &Template, panes: bool) -> io::Result<()> {
+        self.sorted(&Sort::default()).dump_format(w, template, panes)
+    }
+
+    fn dump_json<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.sorted(&Sort::default()).dump_json(w)
+    }
+
+    fn dump_json_lines<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.sorted(&Sort::default()).dump_json_lines(w)
+    }
+
+    fn get_cmd(&self, tmux: &dyn TmuxBackend, get: &Get) -> Result<(), Error> {
+        plan::execute(tmux, &self.plan(&Action::Get(get.clone()))?)
+    }
+
+    fn attach_cmd(&self, tmux: &dyn TmuxBackend, attach: &Attach) -> Result<(), Error> {
+        plan::execute(tmux, &self.plan(&Action::Attach(attach.clone()))?)
+    }
+
+    fn go_cmd(&self, tmux: &dyn TmuxBackend, attach: &Attach) -> Result<(), Error> {
+        plan::execute(tmux, &self.plan(&Action::Go(attach.clone()))?)
+    }
+
+    fn switch_cmd(&self, tmux: &dyn TmuxBackend) -> Result<(), Error> {
+        plan::execute(tmux, &self.plan(&Action::Switch)?)
+    }
+
+    fn rename_cmd(&self, tmux: &dyn TmuxBackend, name: &str) -> Result<(), Error> {
+        plan::execute(tmux, &self.plan(&Action::Rename(name.to_string()))?)
+    }
+
+    fn rename_session_cmd(&self, tmux: &dyn TmuxBackend, name: &str) -> Result<(), Error> {
+        plan::execute(tmux, &self.plan(&Action::RenameSession(name.to_string()))?)
+    }
+
+    fn kill_cmd(&self, tmux: &dyn TmuxBackend) -> Result<(), Error> {
+        plan::execute(tmux, &self.plan(&Action::Kill)?)
+    }
+
+    fn kill_session_cmd(&self, tmux: &dyn TmuxBackend) -> Result<(), Error> {
+        plan::execute(tmux, &self.plan(&Action::KillSession)?)
+    }
+
+    fn send_cmd(&self, tmux: &dyn TmuxBackend, keys: &Keys) -> Result<(), Error> {
+        plan::execute(tmux, &self.plan(&Action::Send(keys.clone()))?)
+    }
+
+    fn plan(&self, action: &Action) -> Result<Vec<Step>, Error> {
+        let step = match action {
+            Action::Kill | Action::KillSession => return self.plan_all(action),
+            Action::Get(get) => {
+                let (session, tab) = single_tab(self)?;
+                get_step(session, tab, get)
+            }
+            Action::Attach(attach) => {
+                let (session, window) = single_match(self)?;
+                let target = session.focus_target(window);
+                let args = enter_args(&target, attach, &session.server);
+                enter_step(&session.name, &session.server, &args, attach)
+            }
+            Action::Switch => {
+                let (session, window) = single_match(self)?;
+                let args = ["switch-client", "-t", &session.focus_target(window)];
+                Step::new(&session.name, &session.server, &args)
+            }
+            Action::Go(attach) => {
+                let (session, tab) = single_tab(self)?;
+                let window = session.window_target(tab);
+                let pane = match tab.panes.as_slice() {
+                    [pane] => Some(session.pane_target(tab, pane)),
+                    _ => None,
+                };
+                let target = session.target();
+
+                // One invocation, with tmux's `;` between commands, since
+                // attaching hands this process over to tmux.
+                let mut args = vec!["select-window", "-t", &window];
+                if let Some(ref pane) = pane {
+                    args.extend(&[";", "select-pane", "-t", pane]);
+                }
+                args.push(";");
+                args.extend(enter_args(&target, attach, &session.server));
+                enter_step(&window_label(session, tab), &session.server, &args, attach)
+            }
+            Action::Rename(name) => {
+                let (session, tab) = single_tab(self)?;
+                let args = ["rename-window", "-t", &session.window_target(tab), name];
+                Step::new(&window_label(session, tab), &session.server, &args)
+            }
+            Action::RenameSession(name) => {
+                let (session, _) = single_match(self)?;
+                let args = ["rename-session", "-t", &session.target(), name];
+                Step::new(&session.name, &session.server, &args)
+            }
+            Action::Send(keys) => {
+                let (session, tab) = single_tab(self)?;
+                return Ok(send_steps(session, tab, keys));
+            }
+        };
+        Ok(vec![step])
+    }
+
+    fn plan_all(&self, action: &Action) -> Result<Vec<Step>, Error> {
+        match action {
+            Action::Get(get) if get.mode != GetMode::Swap => {}
+            Action::Kill | Action::KillSession | Action::Send(_) => {}
+            _ => {
+                return Err(Error::Usage(
+                    "only getting, killing and sending keys can be done to every match".to_string(),
+                ))
+            }
+        }
+        // Only the first window could have the index, and every other move
+        // would fail for want of it.
+        if let Action::Get(Get { index: Some(_), .. }) = action {
+            return Err(Error::Usage("every match can't go at the same --index".to_string()));
+        }
+        if self.is_empty() {
+            return Err(Error::NoMatch);
+        }
+
+        let mut plan = vec![];
+        for (session, window) in self.sorted(&Sort::default()).0 {
+            if let Action::KillSession = action {
+                let args = ["kill-session", "-t", &session.target()];
+                plan.push(Step::new(&session.name, &session.server, &args));
+                continue;
+            }
+            // Last first, so that taking a window out of its session doesn't
+            // renumber the rest under renumber-windows.
+            for tab in window.tabs.iter().rev() {
+                match action {
+                    Action::Get(get) => plan.push(get_step(session, tab, get)),
+                    Action::Kill => {
+                        let args = ["kill-window", "-t", &session.window_target(tab)];
+                        plan.push(Step::new(&window_label(session, tab), &session.server, &args));
+                    }
+                    Action::Send(keys) => plan.extend(send_steps(session, tab, keys)),
+                    _ => unreachable!("checked above"),
+                }
+            }
+        }
+        Ok(plan)
+    }
+
+    fn select_tabs(&self, search: &Search) -> WindowList {
+        let mut out: WindowList = HashMap::new();
+        for (session, window) in self.iter() {
+            let mut _win: Window = window.without_tabs();
+            for tab in window.tabs.iter() {
+                if search.matches(tab) {
+                    _win.push(search.narrow(tab));
+                }
+            }
+            if !_win.is_empty() {
+                out.insert(session.clone(), _win);
+            }
+        }
+        out
+    }
+
+    fn ranked<'a>(&'a self, search: &Search) -> Vec<Match<'a>> {
+        let mut matches = vec![];
+        for (session, window) in self.iter() {
+            for tab in window.tabs.iter() {
+                if let Some(score) = search.score(tab) {
+                    let panes = search.panes(tab);
+                    matches.push(Match { session, window, tab, panes, score });
+                }
+            }
+        }
+        // Break ties by position, so that equal scores come out the same
+        // way every time rather than in hash order.
+        matches.sort_by(|a, b| {
+            b.score.cmp(&a.score)
+                .then_with(|| a.session.name.cmp(&b.session.name))
+                .then_with(|| a.session.server.cmp(&b.session.server))
+                .then_with(|| a.session.id.cmp(&b.session.id))
+                .then_with(|| a.tab.number.cmp(&b.tab.number))
+        });
+        matches
+    }
+
+    fn populate(&mut self, tmux: &dyn TmuxBackend, server: &Server) -> Result<(), Error> {
+        // Anything which turns up here without a session or window to go
+        // in was created since we listed those, and so is skipped.
+        for record in list::<WindowRecord>(tmux, server, &["list-windows", "-a"])? {
+            let window = self.iter_mut()
+                .find(|(session, _)| &session.server == server && session.id == record.session_id)
+                .map(|(_, window)| window);
+            if let Some(window) = window {
+                window.push(record.tab);
+            }
+        }
+
+        for record in list::<PaneRecord>(tmux, server, &["list-panes", "-a"])? {
+            let tab = self.iter_mut()
+                .find(|(session, _)| &session.server == server && session.id == record.session_id)
+                .and_then(|(_, window)| {
+                    window.tabs.iter_mut().find(|tab| tab.number == record.window_index)
+                });
+            if let Some(tab) = tab {
+                tab.panes.push(record.pane);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{MatchMode, Pattern};
+    use crate::sort::SortKey;
+    use crate::testing::{calls_since, created, line, server};
+    use crate::tmux::FakeBackend;
+
+    fn session<'a>(windows: &'a WindowList, name: &str) -> &'a Window {
+        windows.iter()
+            .find(|(session, _)| session.name == name)
+            .map(|(_, window)| window)
+            .expect("session should exist")
+    }
+
+    #[test]
+    fn test_build_windowlist() {
+        let tmux = server();
+        let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+
+        assert_eq!(windows.len(), 2);
+        let work = session(&windows, "work");
+        assert!(work.attached);
+        assert_eq!(work.tabs.len(), 2);
+        assert_eq!(work.tabs[1].name, "vim main.rs");
+        assert_eq!(work.tabs[1].panes.len(), 2);
+        assert_eq!(work.tabs[1].panes[1].command, "cargo");
+        assert_eq!(work.tabs[1].panes[1].pid, 102);
+        let dev = session(&windows, "api: dev ü");
+        assert!(!dev.attached);
+        assert_eq!(dev.tabs[0].number, 3);
+    }
+
+    #[test]
+    fn test_build_windowlist_without_sessions() {
+        let tmux = FakeBackend::new()
+            .respond("list-sessions", "")
+            .fail("list-windows", "no current target");
+        assert!(build_windowlist(&tmux, &Server::Default).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_build_windowlist_rejects_malformed_output() {
+        let tmux = FakeBackend::new()
+            .respond("list-sessions", &line(&["$0", "work", "lots", "1", "1000", "3000", "3000"]));
+        assert!(build_windowlist(&tmux, &Server::Default).is_err());
+    }
+
+    #[test]
+    fn test_build_serverlist() {
+        let tmux = server();
+        let servers = [Server::Name("one".to_string()), Server::Name("two".to_string())];
+        let windows = build_serverlist(&tmux, &servers).unwrap();
+        assert_eq!(windows.len(), 4);
+
+        let searched = windows.select_tabs(&Search::name("logs"));
+        assert_eq!(searched.len(), 2);
+        let mut out = vec![];
+        searched.dump(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Server: one\n  Session: api: dev ü\n    3: logs*! [80x24]\n"));
+        assert!(out.contains("Server: two\n  Session: api: dev ü\n    3: logs*! [80x24]\n"));
+
+        let ranked = windows.ranked(&Search::name("logs"));
+        assert_eq!(match_labels(&ranked), vec!["[one] api: dev ü:3: logs", "[two] api: dev ü:3: logs"]);
+        assert_eq!(match_labels(&ranked[..1]), vec!["api: dev ü:3: logs"]);
+
+        let searched = windows.select_tabs(&Search::name("vim"))
+            .into_iter()
+            .filter(|(session, _)| session.server == servers[1])
+            .collect::<WindowList>();
+        searched.get_cmd(&tmux, &Get::default()).unwrap();
+        assert_eq!(
+            tmux.calls().last().unwrap(),
+            &vec!["-L", "two", "move-window", "-s", "$0:1"]
+        );
+    }
+
+    #[test]
+    fn test_build_serverlist_without_servers() {
+        let tmux = FakeBackend::new()
+            .fail("list-sessions", "no server running on /tmp/tmux-1000/stale");
+        let stale = [Server::Name("stale".to_string())];
+        for servers in [&stale[..], &[]].iter() {
+            match build_serverlist(&tmux, servers) {
+                Err(Error::NoServer(_)) => {}
+                other => panic!("expected NoServer, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_select_tabs() {
+        let windows = build_windowlist(&server(), &Server::Default).unwrap();
+
+        let searched = windows.select_tabs(&Search::name("vim"));
+        assert_eq!(searched.len(), 1);
+        let work = session(&searched, "work");
+        assert!(work.attached);
+        assert_eq!(work.tabs.len(), 1);
+        assert_eq!(work.tabs[0].number, 1);
+
+        assert!(windows.select_tabs(&Search::name("emacs")).is_empty());
+        assert_eq!(windows.select_tabs(&Search::name("s")).len(), 2);
+    }
+
+    #[test]
+    fn test_select_tabs_by_pane() {
+        let windows = build_windowlist(&server(), &Server::Default).unwrap();
+
+        let by_command = Search {
+            command: Some("cargo".to_string()),
+            ..Search::default()
+        };
+        let searched = windows.select_tabs(&by_command);
+        let work = session(&searched, "work");
+        assert_eq!(work.tabs[0].name, "vim main.rs");
+        assert_eq!(work.tabs[0].panes.len(), 1);
+        assert_eq!(work.tabs[0].panes[0].command, "cargo");
+
+        let by_path = Search {
+            path: Some("/var/log".to_string()),
+            ..Search::default()
+        };
+        let searched = windows.select_tabs(&by_path);
+        assert_eq!(session(&searched, "api: dev ü").tabs[0].name, "logs");
+
+        let combined = Search {
+            name: Some(Pattern::substring("zsh")),
+            path: Some("/src/tinfo".to_string()),
+            ..Search::default()
+        };
+        assert!(windows.select_tabs(&combined).is_empty());
+    }
+
+    #[test]
+    fn test_ranked() {
+        let windows = build_windowlist(&server(), &Server::Default).unwrap();
+        let fuzzy = |term| Search {
+            name: Some(Pattern::new(term, MatchMode::Fuzzy, false).unwrap()),
+            ..Search::default()
+        };
+
+        let ranked = windows.ranked(&fuzzy("vmr"));
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(clear_winner(&ranked).unwrap().tab.name, "vim main.rs");
+
+        // Equal scores come out by session, then window.
+        let ranked = windows.ranked(&fuzzy("s"));
+        let names: Vec<_> = ranked.iter().map(|m| m.tab.name.as_str()).collect();
+        assert_eq!(names, vec!["logs", "zsh", "vim main.rs"]);
+        assert!(clear_winner(&ranked).is_none());
+
+        let mut out = vec![];
+        dump_matches(&mut out, &ranked).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "api: dev ü:3: logs\nwork:0: zsh\nwork:1: vim main.rs\n"
+        );
+
+        let tmux = server();
+        let best = clear_winner(&ranked[..1]).unwrap();
+        collect_matches(std::slice::from_ref(best)).get_cmd(&tmux, &Get::default()).unwrap();
+        assert_eq!(
+            tmux.calls().last().unwrap(),
+            &vec!["move-window", "-s", "$1:3"]
+        );
+    }
+
+    #[test]
+    fn test_clear_winner_needs_a_lead() {
+        let windows = build_windowlist(&server(), &Server::Default).unwrap();
+        let mut ranked = windows.ranked(&Search::name("s"));
+        assert_eq!(ranked.len(), 3);
+
+        ranked[0].score = 30;
+        ranked[1].score = 20;
+        assert_eq!(clear_winner(&ranked).unwrap().tab.name, "logs");
+        ranked[0].score = 29;
+        assert!(clear_winner(&ranked).is_none());
+    }
+
+    #[test]
+    fn test_dump() {
+        let windows = build_windowlist(&server(), &Server::Default).unwrap();
+        let mut out = vec![];
+        windows.select_tabs(&Search::name("vim")).dump(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Session: work (attached)\n  1: vim main.rs* [80x24]\n"
+        );
+    }
+
+    #[test]
+    fn test_dump_sorted() {
+        let windows = build_windowlist(&server(), &Server::Default).unwrap();
+        let dump = |sort: Sort| {
+            let mut out = vec![];
+            windows.sorted(&sort).dump(&mut out, false).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let by_name = concat!(
+            "Session: api: dev ü\n  3: logs*! [80x24]\n",
+            "Session: work (attached)\n  0: zsh- [80x24]\n  1: vim main.rs* [80x24]\n",
+        );
+        assert_eq!(dump(Sort::default()), by_name);
+        let by_activity = concat!(
+            "Session: work (attached)\n  0: zsh- [80x24]\n  1: vim main.rs* [80x24]\n",
+            "Session: api: dev ü\n  3: logs*! [80x24]\n",
+        );
+        assert_eq!(dump(Sort { key: SortKey::Activity, reverse: false }), by_activity);
+        assert_eq!(dump(Sort { key: SortKey::Name, reverse: true }), by_activity);
+        assert_eq!(dump(Sort { key: SortKey::Created, reverse: false }), by_activity);
+    }
+
+    #[test]
+    fn test_dump_panes() {
+        let windows = build_windowlist(&server(), &Server::Default).unwrap();
+        let mut out = vec![];
+        windows.select_tabs(&Search::name("vim")).dump_panes(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                "Session: work (attached), created 1970-01-01T00:16:40Z, ",
+                "last attached 1970-01-01T00:50:00Z\n",
+                "  1: vim main.rs* [80x24] [layout a3f1,80x24,0,0[80x12,0,0,1,80x11,0,13,2]], ",
+                "active 1970-01-01T00:50:00Z\n",
+                "    0: vim /src/tinfo [40x24] (active)\n",
+                "    1: cargo /src/tinfo [39x24]\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_dump_format() {
+        let windows = build_windowlist(&server(), &Server::Default).unwrap();
+        let searched = windows.select_tabs(&Search::name("vim"));
+
+        let template = Template::parse("{session}:{window.index:>2} {window.name:12}|{?attached:*}").unwrap();
+        let mut out = vec![];
+        searched.dump_format(&mut out, &template, false).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "work: 1 vim main.rs |*\n");
+
+        let template = Template::parse("{window.index}.{pane.index} {pane.command}{?pane.active: <}").unwrap();
+        let mut out = vec![];
+        searched.dump_format(&mut out, &template, true).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1.0 vim <\n1.1 cargo\n");
+    }
+
+    #[test]
+    fn test_dump_json() {
+        let windows = build_windowlist(&server(), &Server::Default).unwrap();
+        let searched = windows.select_tabs(&Search::name("logs"));
+
+        let mut out = vec![];
+        searched.dump_json(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                r#"{"version":1,"sessions":[{"server":"default","id":"$1","name":"api: dev ü","#,
+                r#""attached":false,"created":2000,"activity":2500,"last_attached":null,"#,
+                r#""windows":[{"index":3,"name":"logs","width":80,"height":24,"#,
+                r#""layout":"c1d4,80x24,0,0,3","activity":2500,"active":true,"last":false,"#,
+                r#""zoomed":false,"bell":true,"silence":false,"panes":[{"index":0,"id":"%3","#,
+                r#""active":true,"pid":103,"width":80,"height":24,"command":"tail","#,
+                r#""path":"/var/log","title":"tail"}]}]}]}"#,
+                "\n"
+            )
+        );
+
+        let mut out = vec![];
+        searched.dump_json_lines(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                r#"{"version":1,"session":{"server":"default","id":"$1","name":"api: dev ü","#,
+                r#""attached":false,"created":2000,"activity":2500,"last_attached":null},"#,
+                r#""window":{"index":3,"name":"logs","width":80,"height":24,"#,
+                r#""layout":"c1d4,80x24,0,0,3","activity":2500,"active":true,"last":false,"#,
+                r#""zoomed":false,"bell":true,"silence":false,"panes":[{"index":0,"id":"%3","#,
+                r#""active":true,"pid":103,"width":80,"height":24,"command":"tail","#,
+                r#""path":"/var/log","title":"tail"}]}}"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_get_cmd() {
+        let tmux = server();
+        let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+        windows.select_tabs(&Search::name("logs")).get_cmd(&tmux, &Get::default()).unwrap();
+        assert_eq!(
+            tmux.calls().last().unwrap(),
+            &vec!["move-window", "-s", "$1:3"]
+        );
+    }
+
+    #[test]
+    fn test_get_cmd_modes() {
+        let tmux = server();
+        let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+        let searched = windows.select_tabs(&Search::name("logs"));
+        let run = |get: Get| {
+            searched.get_cmd(&tmux, &get).unwrap();
+            tmux.calls().last().unwrap().clone()
+        };
+
+        let link = Get { mode: GetMode::Link, index: Some(5), ..Get::default() };
+        assert_eq!(run(link.clone()), vec!["link-window", "-s", "$1:3", "-t", ":5"]);
+        let swap = Get { mode: GetMode::Swap, focus: false, ..Get::default() };
+        assert_eq!(run(swap), vec!["swap-window", "-d", "-s", "$1:3"]);
+        let moved = Get { index: Some(0), focus: false, ..Get::default() };
+        assert_eq!(run(moved), vec!["move-window", "-d", "-s", "$1:3", "-t", ":0"]);
+        let unlink = Get { mode: GetMode::Unlink, ..link };
+        assert_eq!(run(unlink), vec!["unlink-window", "-t", "$1:3"]);
+        let into = Get { into: Some("ops".to_string()), ..Get::default() };
+        assert_eq!(run(into.clone()), vec!["move-window", "-s", "$1:3", "-t", "=ops:"]);
+        let into = Get { index: Some(2), ..into };
+        assert_eq!(run(into), vec!["move-window", "-s", "$1:3", "-t", "=ops:2"]);
+    }
+
+    #[test]
+    fn test_get_cmd_ambiguous() {
+        let tmux = server();
+        let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+        match windows.select_tabs(&Search::name("s")).get_cmd(&tmux, &Get::default()) {
+            Err(Error::Ambiguous(3)) => {}
+            other => panic!("expected Ambiguous(3), got {:?}", other),
+        }
+        match windows.select_tabs(&Search::name("vim")).attach_cmd(&tmux, &Attach::default()) {
+            Ok(()) => {}
+            other => panic!("expected to attach, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_get_cmd_no_match() {
+        let tmux = server();
+        let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+        match windows.select_tabs(&Search::name("emacs")).get_cmd(&tmux, &Get::default()) {
+            Err(Error::NoMatch) => {}
+            other => panic!("expected NoMatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_command_failures() {
+        let tmux = server().fail("move-window", "can't find window: 3\n");
+        let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+        match windows.select_tabs(&Search::name("logs")).get_cmd(&tmux, &Get::default()) {
+            Err(Error::Tmux(message)) => assert_eq!(message, "can't find window: 3"),
+            other => panic!("expected a tmux error, got {:?}", other),
+        }
+
+        let tmux = server().fail("switch-client", "no current client\n");
+        let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+        let attach = Attach { inside: Some(Server::Default), detach_others: false };
+        match windows.select_tabs(&Search::name("logs")).attach_cmd(&tmux, &attach) {
+            Err(Error::Tmux(message)) => assert_eq!(message, "no current client"),
+            other => panic!("expected a tmux error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_switch_cmd() {
+        let tmux = server();
+        let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+        windows.select_tabs(&Search::name("logs")).switch_cmd(&tmux).unwrap();
+        assert_eq!(
+            tmux.calls().last().unwrap(),
+            &vec!["switch-client", "-t", "$1:3.0"]
+        );
+    }
+
+    #[test]
+    fn test_attach_cmd() {
+        let tmux = server();
+        let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+        windows.select_tabs(&Search::name("zsh")).attach_cmd(&tmux, &Attach::default()).unwrap();
+        assert_eq!(
+            tmux.calls().last().unwrap(),
+            &vec!["attach-session", "-t", "$0:0.0"]
+        );
+
+        // Only the window is selected when it has several panes, unless a
+        // pane selector picked one of them out.
+        let attach = Attach { detach_others: true, ..Attach::default() };
+        windows.select_tabs(&Search::name("vim")).attach_cmd(&tmux, &attach).unwrap();
+        assert_eq!(
+            tmux.calls().last().unwrap(),
+            &vec!["attach-session", "-d", "-t", "$0:1"]
+        );
+        let by_command = Search { command: Some("cargo".to_string()), ..Search::default() };
+        windows.select_tabs(&by_command).attach_cmd(&tmux, &attach).unwrap();
+        assert_eq!(
+            tmux.calls().last().unwrap(),
+            &vec!["attach-session", "-d", "-t", "$0:1.1"]
+        );
+
+        // Several windows in a session leave the session to pick.
+        let either = Search {
+            name: Some(Pattern::new("zsh|vim", MatchMode::Regex, false).unwrap()),
+            ..Search::default()
+        };
+        windows.select_tabs(&either).attach_cmd(&tmux, &attach).unwrap();
+        assert_eq!(
+            tmux.calls().last().unwrap(),
+            &vec!["attach-session", "-d", "-t", "$0"]
+        );
+    }
+
+    #[test]
+    fn test_go_cmd() {
+        let tmux = server();
+        let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+
+        windows.select_tabs(&Search::name("vim")).go_cmd(&tmux, &Attach::default()).unwrap();
+        assert_eq!(
+            tmux.calls().last().unwrap(),
+            &vec!["select-window", "-t", "$0:1", ";", "attach-session", "-t", "$0"]
+        );
+
+        let by_command = Search { command: Some("cargo".to_string()), ..Search::default() };
+        let attach = Attach { inside: Some(Server::Default), detach_others: false };
+        windows.select_tabs(&by_command).go_cmd(&tmux, &attach).unwrap();
+        assert_eq!(
+            tmux.calls().last().unwrap(),
+            &vec![
+                "select-window", "-t", "$0:1", ";",
+                "select-pane", "-t", "$0:1.1", ";",
+                "switch-client", "-t", "$0",
+            ]
+        );
+
+        match windows.select_tabs(&Search::name("s")).go_cmd(&tmux, &attach) {
+            Err(Error::Ambiguous(3)) => {}
+            other => panic!("expected Ambiguous(3), got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_attach_cmd_inside_tmux() {
+        let tmux = server();
+        let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+        let searched = windows.select_tabs(&Search::name("logs"));
+
+        let attach = Attach { inside: Some(Server::Default), detach_others: true };
+        searched.attach_cmd(&tmux, &attach).unwrap();
+        assert_eq!(
+            tmux.calls().last().unwrap(),
+            &vec!["switch-client", "-t", "$1:3.0"]
+        );
+
+        // A client on another server can't be switched over.
+        let attach = Attach { inside: Some(Server::Name("other".to_string())), ..attach };
+        searched.attach_cmd(&tmux, &attach).unwrap();
+        assert_eq!(
+            tmux.calls().last().unwrap(),
+            &vec!["attach-session", "-d", "-t", "$1:3.0"]
+        );
+    }
+
+    #[test]
+    fn test_rename_cmd() {
+        let tmux = server();
+        let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+        windows.select_tabs(&Search::name("vim")).rename_cmd(&tmux, "editor").unwrap();
+        assert_eq!(
+            tmux.calls().last().unwrap(),
+            &vec!["rename-window", "-t", "$0:1", "editor"]
+        );
+        windows.select_tabs(&Search::name("logs")).rename_session_cmd(&tmux, "ops").unwrap();
+        assert_eq!(
+            tmux.calls().last().unwrap(),
+            &vec!["rename-session", "-t", "$1", "ops"]
+        );
+
+        // Renaming needs a single window; a session may match on several.
+        let every = Search {
+            name: Some(Pattern::new("zsh|vim", MatchMode::Regex, false).unwrap()),
+            ..Search::default()
+        };
+        assert!(windows.select_tabs(&every).rename_cmd(&tmux, "x").is_err());
+        windows.select_tabs(&every).rename_session_cmd(&tmux, "home").unwrap();
+        assert_eq!(tmux.calls().last().unwrap()[2], "$0");
+    }
+
+    #[test]
+    fn test_kill_cmd() {
+        let tmux = server();
+        let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+        let before = tmux.calls().len();
+        windows.kill_cmd(&tmux).unwrap();
+        let calls = calls_since(&tmux, before);
+        assert_eq!(calls, vec![
+            "kill-window -t $1:3",
+            "kill-window -t $0:1",
+            "kill-window -t $0:0",
+        ]);
+
+        let before = tmux.calls().len();
+        windows.select_tabs(&Search::name("m")).kill_session_cmd(&tmux).unwrap();
+        let calls = calls_since(&tmux, before);
+        assert_eq!(calls, vec!["kill-session -t $0"]);
+
+        match windows.select_tabs(&Search::name("nope")).kill_cmd(&tmux) {
+            Err(Error::NoMatch) => {}
+            other => panic!("expected NoMatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_send_cmd() {
+        let tmux = server();
+        let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+        let vim = windows.select_tabs(&Search::name("vim"));
+        let keys = Keys { keys: vec!["C-c".to_string()], each_pane: false };
+        vim.send_cmd(&tmux, &keys).unwrap();
+        assert_eq!(tmux.calls().last().unwrap(), &vec!["send-keys", "-t", "$0:1", "C-c"]);
+
+        let keys = Keys { each_pane: true, ..keys };
+        let before = tmux.calls().len();
+        vim.send_cmd(&tmux, &keys).unwrap();
+        let calls = calls_since(&tmux, before);
+        assert_eq!(calls, vec!["send-keys -t $0:1.0 C-c", "send-keys -t $0:1.1 C-c"]);
+    }
+
+    #[test]
+    fn test_plan() {
+        let tmux = server();
+        let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+        let get = Get { into: Some("ops".to_string()), ..Get::default() };
+        let plan = windows.plan_all(&Action::Get(get)).unwrap();
+        let steps: Vec<(&str, String)> = plan.iter()
+            .map(|step| (step.target.as_str(), step.args.join(" ")))
+            .collect();
+        assert_eq!(steps, vec![
+            ("api: dev ü:3: logs", "move-window -s $1:3 -t =ops:".to_string()),
+            ("work:1: vim main.rs", "move-window -s $0:1 -t =ops:".to_string()),
+            ("work:0: zsh", "move-window -s $0:0 -t =ops:".to_string()),
+        ]);
+
+        let plan = windows.plan_all(&Action::KillSession).unwrap();
+        let targets: Vec<&str> = plan.iter().map(|step| step.target.as_str()).collect();
+        assert_eq!(targets, vec!["api: dev ü", "work"]);
+        assert!(tmux.calls().iter().all(|call| !call[0].starts_with("kill")));
+
+        // Only some things make sense done to every match.
+        match windows.plan_all(&Action::Switch) {
+            Err(Error::Usage(_)) => {}
+            other => panic!("expected Usage, got {:?}", other),
+        }
+        match windows.plan_all(&Action::Get(Get { index: Some(4), ..Get::default() })) {
+            Err(Error::Usage(_)) => {}
+            other => panic!("expected Usage, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_plan_attach() {
+        let tmux = server();
+        let windows = build_windowlist(&tmux, &Server::Default).unwrap();
+        let logs = windows.select_tabs(&Search::name("logs"));
+
+        // Attaching hands the terminal over, but switching from inside
+        // tmux doesn't.
+        let plan = logs.plan(&Action::Attach(Attach::default())).unwrap();
+        assert_eq!(plan.len(), 1);
+        assert!(plan[0].exec);
+        assert_eq!(plan[0].args, vec!["attach-session", "-t", "$1:3.0"]);
+        let inside = Attach { inside: Some(Server::Default), ..Attach::default() };
+        let plan = logs.plan(&Action::Go(inside)).unwrap();
+        assert!(!plan[0].exec);
+        assert_eq!(plan[0].target, "api: dev ü:3: logs");
+
+        // Working out a plan runs nothing.
+        let before = tmux.calls().len();
+        logs.plan(&Action::Kill).unwrap();
+        assert_eq!(tmux.calls().len(), before);
+        assert!(windows.plan(&Action::Rename("x".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_new_session() {
+        let tmux = server().respond("new-session", &created("$0", "0", "%0"));
+        let session = new_session(&tmux, &Server::Default, "work", Some("/src"), Some("htop")).unwrap();
+        assert_eq!(session.keys().next().unwrap().name, "work");
+        assert_eq!(
+            tmux.calls()[0],
+            vec!["new-session", "-F", &PaneRecord::format(), "-d", "-P", "-s", "work", "-c", "/src", "htop"]
+        );
+    }
+}
@@ -1,251 +1,640 @@
-#[macro_use]
-extern crate lazy_static;
-#[macro_use]
-extern crate failure;
-
-use std::collections::HashMap;
-use std::process;
+use std::fs::{self, File};
 use std::io::{self, Write};
+use std::process;
 
-use failure::Error;
-use getopts::Options;
-use regex;
-
-#[derive(Debug, Clone)]
-struct Tab {
-    name: String,
-    number: usize,
-    panes: usize,
+use getopts::{Matches, Options};
+
+use tinfo::tmux::{ProcessBackend, Server, TmuxBackend};
+use tinfo::picker::{Prompt, Terminal};
+use tinfo::plan;
+use tinfo::project::{self, Project};
+use tinfo::snapshot;
+use tinfo::search::{MatchMode, Pattern};
+use tinfo::sort::{Sort, SortKey};
+use tinfo::template::Template;
+use tinfo::{
+    build_serverlist, build_windowlist, clear_winner, collect_matches, dump_matches, match_labels,
+    new_session, Action, Attach, Error, Get, GetMode, Keys, Match, Search, WindowSearch,
+};
+
+/// Whether acting on `matches` would need a choice made first. Getting a
+/// window needs a single window; anything else only needs a single session.
+fn is_ambiguous(matches: &[Match], per_window: bool) -> bool {
+    match matches.split_first() {
+        Some((_, rest)) if per_window => !rest.is_empty(),
+        Some((first, rest)) => rest.iter().any(|m| m.session != first.session),
+        None => false,
+    }
 }
 
-impl Tab {
-    fn new(name: &str, number: usize, panes: usize) -> Tab {
-        Tab {
-            name: name.to_string(),
-            number: number,
-            panes: panes,
-        }
-    }
+fn print_usage(opts: &Options) {
+    let brief = "Usage: tinfo [options] [SEARCH]
+       tinfo [options] save FILE
+       tinfo [options] restore FILE
+       tinfo [options] up PROJECT
+       tinfo [options] new NAME
+       tinfo [options] rename [SEARCH] NAME
+       tinfo [options] rename-session [SEARCH] NAME
+       tinfo [options] kill [SEARCH]
+       tinfo [options] kill-session [SEARCH]
+       tinfo [options] send [SEARCH] KEYS...";
+    println!("{}", opts.usage(brief));
 }
 
-#[derive(Debug)]
-struct Window {
-    pub tabs: Vec<Tab>,
-    pub attached: bool,
+fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optflag("G", "get", "Bring matched window here");
+    opts.optflag("l", "link", "Link matched window here, leaving it where it was too");
+    opts.optflag("x", "swap", "Swap matched window with the current one");
+    opts.optflag("u", "unlink", "Unlink matched window from the session it matched in");
+    opts.optopt("t", "index", "Put the window got here at this index", "INDEX");
+    opts.optopt("", "into", "Get windows into this session, not the current one", "SESSION");
+    opts.optflag(
+        "n",
+        "no-focus",
+        "Don't select the window got here, or attach to a project brought up",
+    );
+    opts.optflag(
+        "a",
+        "attach",
+        "Attach to matched session, or switch to it from inside tmux",
+    );
+    opts.optflag("w", "go", "Go straight to matched window, in whichever session");
+    opts.optflag("", "select", "The same as --go");
+    opts.optflag("D", "detach-others", "Detach other clients when attaching");
+    opts.optflag("s", "switch", "Switch this client to matched session");
+    opts.optflag(
+        "",
+        "all",
+        "Get, kill or send keys to every match, rather than needing a single one",
+    );
+    opts.optflag("", "dry-run", "Show the tmux commands tinfo would run, without running them");
+    opts.optopt("L", "socket-name", "Use the tmux server with this socket name", "NAME");
+    opts.optopt("S", "socket-path", "Use the tmux server at this socket path", "PATH");
+    opts.optflag("A", "all-servers", "Search every tmux server you own");
+    opts.optflag("e", "exact", "Match the whole window name");
+    opts.optflag("r", "regex", "Treat SEARCH as a regular expression");
+    opts.optflag("g", "glob", "Treat SEARCH as a shell glob");
+    opts.optflag("f", "fuzzy", "Fuzzy match SEARCH, best matches first");
+    opts.optflag("i", "ignore-case", "Match SEARCH case insensitively");
+    opts.optopt(
+        "c",
+        "command",
+        "Match windows running this command, or run it in a new session",
+        "CMD",
+    );
+    opts.optopt(
+        "d",
+        "dir",
+        "Match windows working in this directory, or start a new session there",
+        "DIR",
+    );
+    opts.optflag("p", "panes", "List the panes of every window");
+    opts.optflag("j", "json", "List as a JSON document");
+    opts.optflag("J", "json-lines", "List as a JSON object per window");
+    opts.optopt(
+        "F",
+        "format",
+        "List a line per window (or pane, with --panes) from a template such as \
+         '{session}:{window.index} {window.name}'",
+        "TEMPLATE",
+    );
+    opts.optopt(
+        "O",
+        "sort",
+        "List sessions by name (the default), activity, created, panes or attached",
+        "KEY",
+    );
+    opts.optflag("R", "reverse", "List sessions in reverse order");
+    opts.optflag("h", "help", "Show this help");
+    opts
 }
 
-impl Window {
-    fn new(tabs: Vec<Tab>, attached: bool) -> Window {
-        Window {
-            tabs: tabs,
-            attached: attached,
+fn main() {
+    let opts = options();
+    let args: Vec<_> = std::env::args().collect();
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            println!("{}\n", f);
+            print_usage(&opts);
+            process::exit(1);
         }
-    }
+    };
 
-    fn push(&mut self, tab: Tab) {
-        self.tabs.push(tab);
+    if matches.opt_present("h") {
+        print_usage(&opts);
+        return;
     }
 
-    fn is_empty(&self) -> bool {
-        return self.tabs.len() == 0;
+    if let Err(e) = dispatch(matches, &ProcessBackend, &Terminal, &mut io::stdout()) {
+        eprintln!("tinfo: {}", e);
+        if let Error::Usage(_) = e {
+            print_usage(&opts);
+        }
+        process::exit(e.exit_code());
     }
 }
 
-type WindowList = HashMap<usize, Window>;
-
-trait WindowSearch {
-    fn select_tabs(&self, searchterm: &str) -> Self;
-    fn populate(&mut self) -> Result<(), Error>;
-    fn dump<W: Write>(&self, w: &mut W) -> io::Result<()>;
-    fn get_cmd(&self) -> Result<(), Error>;
-    fn attach_cmd(&self) -> Result<(), Error>;
+/// The things tinfo does besides searching.
+const COMMANDS: &[&str] = &["save", "restore", "up", "new"];
+
+/// The things tinfo does with what it found, besides listing it.
+const SEARCH_COMMANDS: &[&str] = &["rename", "rename-session", "kill", "kill-session", "send"];
+
+/// Do whatever `matches` asks, with `tmux`, asking `prompt` whenever
+/// there's a choice to be made and writing anything to show to `out`.
+fn dispatch<W: Write>(
+    mut matches: Matches,
+    tmux: &dyn TmuxBackend,
+    prompt: &dyn Prompt,
+    out: &mut W,
+) -> Result<(), Error> {
+    // Subcommands take the place of SEARCH, and the same options it does.
+    match matches.free.first() {
+        Some(command) if COMMANDS.contains(&command.as_str()) => {
+            let command = matches.free.remove(0);
+            run_command(&command, &matches, tmux)
+        }
+        Some(command) if SEARCH_COMMANDS.contains(&command.as_str()) => {
+            let command = matches.free.remove(0);
+            run(&matches, Some(&command), tmux, prompt, out)
+        }
+        _ => run(&matches, None, tmux, prompt, out),
+    }
 }
 
-fn build_windowlist() -> Result<WindowList, Error> {
-    lazy_static! {
-        static ref SESSION_RE: regex::Regex =
-            regex::Regex::new(r"^(\d+) (\d+) (\d+)")
-                .expect("Compiling regex");
+/// The server picked with `-L` or `-S`, or the one we're inside of.
+fn server(matches: &Matches) -> Server {
+    if let Some(path) = matches.opt_str("S") {
+        Server::Path(path.into())
+    } else if let Some(name) = matches.opt_str("L") {
+        Server::Name(name)
+    } else {
+        Server::from_env()
     }
+}
 
-    let out = process::Command::new("tmux")
-        .arg("list-sessions")
-        .arg("-F").arg("#{session_name} #{session_windows} #{session_attached}")
-        .output()?;
-    let mut windows: WindowList = HashMap::new();
+fn run_command(command: &str, matches: &Matches, tmux: &dyn TmuxBackend) -> Result<(), Error> {
+    let operand = match (command, matches.free.as_slice()) {
+        (_, [operand]) => operand,
+        ("up", _) => return Err(Error::Usage("up needs a single PROJECT".to_string())),
+        ("new", _) => return Err(Error::Usage("new needs a single NAME".to_string())),
+        _ => return Err(Error::Usage(format!("{} needs a single FILE", command))),
+    };
+    if matches.opt_present("dry-run") {
+        return Err(Error::Usage(format!("--dry-run doesn't work with {}", command)));
+    }
+    let server = server(matches);
+    match command {
+        "save" => {
+            let windows = snapshot::save(tmux, &server)?;
+            snapshot::write(&mut File::create(operand)?, &windows)?;
+        }
+        "restore" => {
+            let saved = snapshot::read(&fs::read_to_string(operand)?)?;
+            for name in snapshot::restore(tmux, &server, &saved)? {
+                eprintln!("tinfo: session {:?} already exists, leaving it alone", name);
+            }
+        }
+        "up" => {
+            let project = Project::load(&project::find(operand))?;
+            let session = project::up(tmux, &server, &project)?;
+            if !matches.opt_present("n") {
+                session.attach_cmd(tmux, &Attach::from_env(matches.opt_present("D")))?;
+            }
+        }
+        "new" => {
+            let dir = matches.opt_str("d");
+            let shell_command = matches.opt_str("c");
+            let session = new_session(tmux, &server, operand, dir.as_deref(), shell_command.as_deref())?;
+            if !matches.opt_present("n") {
+                session.attach_cmd(tmux, &Attach::from_env(matches.opt_present("D")))?;
+            }
+        }
+        _ => unreachable!("unknown command {:?}", command),
+    }
+    Ok(())
+}
 
-    for line in String::from_utf8_lossy(&out.stdout).split('\n') {
-        if line == "" {
-            break;
+fn run<W: Write>(
+    matches: &Matches,
+    command: Option<&str>,
+    tmux: &dyn TmuxBackend,
+    prompt: &dyn Prompt,
+    out: &mut W,
+) -> Result<(), Error> {
+    // Renaming takes the new name after any SEARCH, and sending the keys.
+    // Those are every argument but the first, if there are more than one,
+    // unless --command or --dir have picked the panes to send them to.
+    let mut free = matches.free.clone();
+    let mut keys = vec![];
+    let operand = match command {
+        Some(command @ "rename") | Some(command @ "rename-session") => match free.len() {
+            1 | 2 => free.pop(),
+            _ => return Err(Error::Usage(format!("{} needs a NAME, after any SEARCH", command))),
+        },
+        Some("send") if free.is_empty() => {
+            return Err(Error::Usage("send needs KEYS, after any SEARCH".to_string()))
+        }
+        Some("send") => {
+            let searched = free.len() > 1 && !matches.opt_present("c") && !matches.opt_present("d");
+            keys = free.split_off(if searched { 1 } else { 0 });
+            None
+        }
+        Some(command) if free.len() > 1 => {
+            return Err(Error::Usage(format!("{} takes a single SEARCH", command)))
         }
+        _ => None,
+    };
 
-        let cap = SESSION_RE.captures(&line)
-            .ok_or(format_err!("Couldn't match line"))?;
-        let id: usize = cap[1].parse()?;
-        let num_windows: usize = cap[2].parse()?;
-        let attached: usize = cap[3].parse()?;
-        let vec = Vec::with_capacity(num_windows);
-        windows.insert(id, Window::new(vec, attached > 0));
+    let modes: Vec<_> = [
+        ("e", MatchMode::Exact),
+        ("r", MatchMode::Regex),
+        ("g", MatchMode::Glob),
+        ("f", MatchMode::Fuzzy),
+    ]
+        .iter()
+        .filter(|(opt, _)| matches.opt_present(opt))
+        .map(|(_, mode)| *mode)
+        .collect();
+    if modes.len() > 1 {
+        return Err(Error::Usage(
+            "only one of --exact, --regex, --glob and --fuzzy may be given".to_string(),
+        ));
     }
+    let mode = modes.first().cloned().unwrap_or_default();
 
-    windows.populate()?;
+    let name = match free.first() {
+        Some(term) => Some(Pattern::new(term, mode, matches.opt_present("i"))?),
+        None => None,
+    };
 
-    return Ok(windows);
-}
+    let template = match matches.opt_str("F") {
+        Some(source) => Some(Template::parse(&source)?),
+        None => None,
+    };
 
-impl WindowSearch for WindowList {
-    fn dump<W: Write>(&self, w: &mut W) -> io::Result<()> {
-        // TODO(richo) Check results
-        for (idx, window) in self.iter() {
-            write!(w, "Session: {}", idx)?;
-            if window.attached {
-                write!(w, " (attached)")?;
-            }
-            write!(w, "\n")?;
-            for tab in window.tabs.iter() {
-                write!(w, "  {}: {}\n", tab.number, tab.name)?;
-            }
-        }
-        Ok(())
+    let get_modes: Vec<_> = [
+        ("G", GetMode::Move),
+        ("l", GetMode::Link),
+        ("x", GetMode::Swap),
+        ("u", GetMode::Unlink),
+    ]
+        .iter()
+        .filter(|(opt, _)| matches.opt_present(opt))
+        .map(|(_, mode)| *mode)
+        .collect();
+    if get_modes.len() > 1 {
+        return Err(Error::Usage(
+            "only one of --get, --link, --swap and --unlink may be given".to_string(),
+        ));
     }
+    let get = match get_modes.first() {
+        Some(&mode) => Some(Get {
+            mode,
+            index: match matches.opt_str("t") {
+                Some(index) => Some(index.parse().map_err(|_| {
+                    Error::Usage(format!("invalid window index {:?}", index))
+                })?),
+                None => None,
+            },
+            into: matches.opt_str("into"),
+            focus: !matches.opt_present("n"),
+        }),
+        None => None,
+    };
 
-    #[must_use]
-    fn get_cmd(&self) -> Result<(), Error> {
-        if self.len() != 1 {
-            panic!("Can only get with a single result");
-        }
+    let sort = Sort {
+        key: match matches.opt_str("O") {
+            Some(key) => key.parse::<SortKey>().map_err(|e| Error::Usage(e.to_string()))?,
+            None => SortKey::default(),
+        },
+        reverse: matches.opt_present("R"),
+    };
 
-        for (idx, window) in self.iter() {
-            if window.tabs.len() != 1 {
-                panic!("Can only get with a single result");
-            }
+    let search = Search {
+        name,
+        command: matches.opt_str("c"),
+        path: matches.opt_str("d"),
+    };
+    let keys = Keys {
+        keys,
+        each_pane: search.has_pane_selectors(),
+    };
 
-            for tab in window.tabs.iter() {
-                process::Command::new("tmux")
-                    .arg("move-window")
-                    .arg("-s")
-                    .arg(format!("{}:{}", idx, tab.number))
-                    .spawn()?;
-                return Ok(());
-            }
-        }
-        Ok(())
+    let going = matches.opt_present("w") || matches.opt_present("select");
+    let attach = Attach::from_env(matches.opt_present("D"));
+    let action = match (command, operand) {
+        (Some("rename"), Some(name)) => Some(Action::Rename(name)),
+        (Some("rename-session"), Some(name)) => Some(Action::RenameSession(name)),
+        (Some("kill"), _) => Some(Action::Kill),
+        (Some("kill-session"), _) => Some(Action::KillSession),
+        (Some("send"), _) => Some(Action::Send(keys)),
+        _ if get.is_some() => get.map(Action::Get),
+        _ if going => Some(Action::Go(attach)),
+        _ if matches.opt_present("a") => Some(Action::Attach(attach)),
+        _ if matches.opt_present("s") => Some(Action::Switch),
+        _ => None,
+    };
+
+    let all = matches.opt_present("all");
+    let dry_run = matches.opt_present("dry-run");
+    if all && !matches!(
+        action,
+        Some(Action::Get(Get { mode: GetMode::Move, .. }))
+            | Some(Action::Get(Get { mode: GetMode::Link, .. }))
+            | Some(Action::Get(Get { mode: GetMode::Unlink, .. }))
+            | Some(Action::Kill)
+            | Some(Action::KillSession)
+            | Some(Action::Send(_))
+    ) {
+        return Err(Error::Usage(
+            "--all works with kill, kill-session, send, --get, --link and --unlink".to_string(),
+        ));
+    }
+    if dry_run && action.is_none() {
+        return Err(Error::Usage("--dry-run needs something to do, like --get or kill".to_string()));
     }
 
-    #[must_use]
-    fn attach_cmd(&self) -> Result<(), Error> {
-        if self.len() != 1 {
-            panic!("Can only get with a single result");
-        }
+    if let (Some(command), true) = (command, search.is_empty()) {
+        return Err(Error::Usage(format!("{} needs a SEARCH, --command or --dir", command)));
+    }
+    if all && search.is_empty() {
+        return Err(Error::Usage("--all needs a SEARCH, --command or --dir".to_string()));
+    }
+    if dry_run && search.is_empty() {
+        return Err(Error::Usage("--dry-run needs a SEARCH, --command or --dir".to_string()));
+    }
+
+    let windows = if matches.opt_present("A") {
+        build_serverlist(tmux, &Server::discover()?)?
+    } else {
+        build_windowlist(tmux, &server(matches))?
+    };
 
-        for (idx, _) in self.iter() {
-            process::Command::new("tmux")
-                .arg("attach-session")
-                .arg("-t")
-                .arg(format!("{}", idx))
-                .spawn()?;
+    if let (true, Some(action)) = (all, &action) {
+        let steps = windows.select_tabs(&search).plan_all(action)?;
+        if dry_run {
+            plan::dump(out, &steps)?;
             return Ok(());
         }
-        Ok(())
+        let outcomes = plan::run_all(tmux, &steps);
+        plan::dump_report(out, &outcomes)?;
+        let failed = outcomes.iter().filter(|(_, outcome)| outcome.is_err()).count();
+        if failed > 0 {
+            return Err(Error::Failed { failed, total: outcomes.len() });
+        }
+        return Ok(());
     }
 
-    fn select_tabs(&self, searchterm: &str) -> WindowList {
-        let mut out: WindowList = HashMap::new();
-        for (idx, window) in self.iter() {
-            let mut _win: Window = Window::new(vec![], window.attached);
-            for tab in window.tabs.iter() {
-                match tab.name.find(searchterm) {
-                    Some(_) => {
-                        let newtab: Tab = (*tab).clone();
-                        _win.push(newtab);
-                    }
-                    None => {}
+    let listing = if !search.is_empty() {
+        let ranked = windows.ranked(&search);
+        // Whether the action is on a window, rather than its session.
+        let per_window = matches!(
+            action,
+            Some(Action::Get(_))
+                | Some(Action::Go(_))
+                | Some(Action::Rename(_))
+                | Some(Action::Kill)
+                | Some(Action::Send(_))
+        );
+        let killing = matches!(action, Some(Action::Kill) | Some(Action::KillSession));
+        let structured = ["j", "J", "F"].iter().any(|opt| matches.opt_present(opt));
+        if mode == MatchMode::Fuzzy && action.is_none() && !structured {
+            dump_matches(out, &ranked)?;
+            return Ok(());
+        }
+
+        // Act on the best match alone if it's clearly the one that was
+        // meant. Otherwise ask which was meant, if there's more than one
+        // candidate and anyone to ask, or list them if not. Killing acts on
+        // every match, once the user says that's what they want, or right
+        // away if it's only being shown.
+        let searched = match clear_winner(&ranked) {
+            Some(best) => collect_matches(std::slice::from_ref(best)),
+            None if killing && dry_run => collect_matches(&ranked),
+            None if killing && is_ambiguous(&ranked, per_window) => {
+                dump_matches(out, &ranked)?;
+                if !prompt.is_interactive() {
+                    return Err(Error::Ambiguous(ranked.len()));
+                }
+                let question = if per_window {
+                    format!("Kill all {} of these windows?", ranked.len())
+                } else {
+                    let found = collect_matches(&ranked);
+                    format!("Kill the {} sessions these windows are in?", found.len())
+                };
+                if !prompt.confirm(&question)? {
+                    return Err(Error::Cancelled);
+                }
+                collect_matches(&ranked)
+            }
+            None if action.is_some() && is_ambiguous(&ranked, per_window) => {
+                if !prompt.is_interactive() {
+                    dump_matches(out, &ranked)?;
+                    return Err(Error::Ambiguous(ranked.len()));
+                }
+                let candidates = match_labels(&ranked);
+                match prompt.pick("Which window?", &candidates)? {
+                    Some(i) => collect_matches(&ranked[i..=i]),
+                    None => return Err(Error::Cancelled),
                 }
             }
-            if !_win.is_empty() {
-                out.insert(*idx, _win);
+            None => collect_matches(&ranked),
+        };
+        if let Some(action) = action {
+            let steps = searched.plan(&action)?;
+            if dry_run {
+                plan::dump(out, &steps)?;
+                return Ok(());
             }
+            return plan::execute(tmux, &steps);
         }
-        return out;
+        searched
+    } else {
+        windows
+    };
+
+    let listing = listing.sorted(&sort);
+    if matches.opt_present("j") {
+        listing.dump_json(out)?;
+    } else if matches.opt_present("J") {
+        listing.dump_json_lines(out)?;
+    } else if let Some(template) = template {
+        listing.dump_format(out, &template, matches.opt_present("p"))?;
+    } else {
+        listing.dump(out, matches.opt_present("p"))?;
     }
 
-    #[must_use]
-    fn populate(&mut self) -> Result<(), Error> {
-        let out = match process::Command::new("tmux")
-            .arg("list-windows")
-            .arg("-a")
-            .output()
-        {
-            Ok(output) => output,
-            Err(e) => panic!("failed to spawn: {}", e),
-        };
-        lazy_static! {
-            static ref WINDOW_RE: regex::Regex =
-                regex::Regex::new(r"^(\d+):(\d+): (.*) \((\d+) panes\) \[(\d+)x(\d+)\]")
-                    .expect("Compiling window regex");
-        }
+    Ok(())
+}
 
-        for line in String::from_utf8_lossy(&out.stdout).split('\n') {
-            if line == "" {
-                return Ok(());
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tinfo::picker::FakePrompt;
+    use tinfo::testing::{calls_since, server};
+    use tinfo::tmux::FakeBackend;
+    use tinfo::WindowList;
+
+    /// Run tinfo with `args` against `tmux`'s `-L t` server, returning how
+    /// it went and what it printed.
+    fn tinfo(args: &[&str], tmux: &FakeBackend, prompt: &FakePrompt) -> (Result<(), Error>, String) {
+        let mut all = vec!["-L", "t"];
+        all.extend(args);
+        let matches = options().parse(&all).unwrap();
+        let mut out = vec![];
+        let result = dispatch(matches, tmux, prompt, &mut out);
+        (result, String::from_utf8(out).unwrap())
+    }
 
-            let cap = WINDOW_RE.captures(&line).expect("Capturing windows");
-            let win_: usize = cap[1].parse()?;
-            let new_tab = Tab::new(
-                &cap[3],
-                cap[2].parse()?,
-                cap[4].parse()?,
-            );
+    /// What tmux was asked to do, besides listing what's there.
+    fn actions(tmux: &FakeBackend) -> Vec<String> {
+        calls_since(tmux, 0)
+            .into_iter()
+            .map(|call| call.trim_start_matches("-L t ").to_string())
+            .filter(|call| !call.starts_with("list-"))
+            .collect()
+    }
 
-            self.get_mut(&win_).unwrap().push(new_tab);
+    fn usage(args: &[&str]) -> String {
+        match tinfo(args, &server(), &FakePrompt::new()).0 {
+            Err(Error::Usage(message)) => message,
+            other => panic!("expected a usage error from {:?}, got {:?}", args, other),
         }
+    }
 
-        Ok(())
+    /// Matches "logs" in `api: dev ü`, and "zsh" and "vim main.rs" in
+    /// `work`, equally well.
+    const AMBIGUOUS: &str = "s";
+
+    #[test]
+    fn test_is_ambiguous() {
+        let windows: WindowList = build_windowlist(&server(), &Server::Default).unwrap();
+        let ranked = windows.ranked(&Search::name(AMBIGUOUS));
+        assert!(is_ambiguous(&ranked, true));
+        assert!(is_ambiguous(&ranked, false));
+        assert!(!is_ambiguous(&ranked[1..2], true));
+        // Both windows are in work, which is all that's needed of a session.
+        assert!(is_ambiguous(&ranked[1..], true));
+        assert!(!is_ambiguous(&ranked[1..], false));
+        assert!(!is_ambiguous(&[], true));
     }
-}
 
-fn print_usage(opts: &Options) {
-    let brief = "Usage: tinfo [options]";
-    println!("{}", opts.usage(&brief));
-}
+    #[test]
+    fn test_clear_winner() {
+        let tmux = server();
+        let prompt = FakePrompt::new();
+        let (result, _) = tinfo(&["-G", "vim"], &tmux, &prompt);
+        assert!(result.is_ok());
+        assert_eq!(actions(&tmux), vec!["move-window -s $0:1"]);
+        assert!(prompt.asked().is_empty());
+    }
 
-fn main() -> Result<(), Error> {
-    let windows = build_windowlist()?;
-    let mut stdout = io::stdout();
+    #[test]
+    fn test_pick() {
+        let tmux = server();
+        let prompt = FakePrompt::new().choosing(Some(2));
+        let (result, out) = tinfo(&["-G", AMBIGUOUS], &tmux, &prompt);
+        assert!(result.is_ok());
+        assert_eq!(out, "");
+        assert_eq!(prompt.asked(), vec!["Which window?"]);
+        assert_eq!(actions(&tmux), vec!["move-window -s $0:1"]);
+
+        let tmux = server();
+        let (result, _) = tinfo(&["-G", AMBIGUOUS], &tmux, &FakePrompt::new().choosing(None));
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert!(actions(&tmux).is_empty());
+    }
 
-    let args: Vec<_> = std::env::args().collect();
-    let mut opts = Options::new();
-    opts.optflag("G", "get", "Bring matched window here");
-    opts.optflag("a", "attach", "Attach to matched session");
-    opts.optflag("h", "help", "Show this help");
+    #[test]
+    fn test_ambiguous_not_interactive() {
+        let tmux = server();
+        let (result, out) = tinfo(&["-G", AMBIGUOUS], &tmux, &FakePrompt::new());
+        let error = result.unwrap_err();
+        assert!(matches!(error, Error::Ambiguous(3)));
+        assert_eq!(error.exit_code(), 2);
+        assert_eq!(out, "api: dev ü:3: logs\nwork:0: zsh\nwork:1: vim main.rs\n");
+        assert!(actions(&tmux).is_empty());
+    }
 
-    let matches = match opts.parse(&args[1..]) {
-        Ok(m) => m,
-        Err(f) => {
-            println!("{}\n", f.to_string());
-            print_usage(&opts);
-            ::std::process::exit(1);
-        }
-    };
+    #[test]
+    fn test_kill_confirmed() {
+        let tmux = server();
+        let prompt = FakePrompt::new().answering(true);
+        let (result, out) = tinfo(&["kill", AMBIGUOUS], &tmux, &prompt);
+        assert!(result.is_ok());
+        assert_eq!(out.lines().count(), 3);
+        assert_eq!(prompt.asked(), vec!["Kill all 3 of these windows?"]);
+        assert_eq!(actions(&tmux), vec![
+            "kill-window -t $1:3",
+            "kill-window -t $0:1",
+            "kill-window -t $0:0",
+        ]);
+    }
 
-    if matches.opt_present("h") {
-        print_usage(&opts);
-        return Ok(());
+    #[test]
+    fn test_kill_declined() {
+        let tmux = server();
+        let prompt = FakePrompt::new().answering(false);
+        let (result, _) = tinfo(&["kill-session", AMBIGUOUS], &tmux, &prompt);
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert_eq!(prompt.asked(), vec!["Kill the 2 sessions these windows are in?"]);
+        assert!(actions(&tmux).is_empty());
     }
 
-    if !matches.free.is_empty() {
-        let searched = windows.select_tabs(&matches.free[0]);
-        if matches.opt_present("G") {
-            searched.get_cmd()?;
-        } else if matches.opt_present("a") {
-            searched.attach_cmd()?;
-        } else {
-            searched.dump(&mut stdout)?;
-        }
-    } else {
-        windows.dump(&mut stdout)?;
+    #[test]
+    fn test_kill_not_interactive() {
+        let tmux = server();
+        let (result, out) = tinfo(&["kill", AMBIGUOUS], &tmux, &FakePrompt::new());
+        assert_eq!(result.unwrap_err().exit_code(), 2);
+        assert_eq!(out.lines().count(), 3);
+        assert!(actions(&tmux).is_empty());
+
+        // Only showing what would be killed doesn't need asking.
+        let tmux = server();
+        let (result, out) = tinfo(&["--dry-run", "kill", AMBIGUOUS], &tmux, &FakePrompt::new());
+        assert!(result.is_ok());
+        assert_eq!(out.lines().count(), 3);
+        assert!(actions(&tmux).is_empty());
     }
 
-    Ok(())
+    #[test]
+    fn test_action() {
+        let dry_run = |args: &[&str]| {
+            let mut all = vec!["--dry-run"];
+            all.extend(args);
+            let tmux = server();
+            let (result, out) = tinfo(&all, &tmux, &FakePrompt::new());
+            result.unwrap();
+            assert!(actions(&tmux).is_empty());
+            out
+        };
+        // Getting a window wins over going to it.
+        assert_eq!(dry_run(&["-G", "-w", "vim"]), "tmux -u -L t move-window -s '$0:1'  # work:1: vim main.rs\n");
+        assert!(dry_run(&["-s", "vim"]).contains(" switch-client -t "));
+        assert!(dry_run(&["rename", "vim", "editor"]).contains(" rename-window -t '$0:1' editor "));
+        assert!(dry_run(&["rename-session", "vim", "w"]).contains(" rename-session -t '$0' w "));
+        assert!(dry_run(&["send", "logs", "C-c"]).contains(" send-keys -t '$1:3' C-c "));
+        assert!(dry_run(&["send", "logs", "C-c", "Enter"]).contains(" send-keys -t '$1:3' C-c Enter "));
+        assert_eq!(
+            dry_run(&["--all", "-c", "cargo", "send", "C-c", "Enter"]),
+            "tmux -u -L t send-keys -t '$0:1.1' C-c Enter  # work:1.1: vim main.rs\n"
+        );
+    }
+
+    #[test]
+    fn test_usage() {
+        assert_eq!(usage(&["-G", "-l", "vim"]), "only one of --get, --link, --swap and --unlink may be given");
+        assert_eq!(usage(&["-e", "-r", "vim"]), "only one of --exact, --regex, --glob and --fuzzy may be given");
+        assert_eq!(usage(&["kill"]), "kill needs a SEARCH, --command or --dir");
+        assert_eq!(usage(&["rename", "a", "b", "c"]), "rename needs a NAME, after any SEARCH");
+        assert_eq!(usage(&["send"]), "send needs KEYS, after any SEARCH");
+        assert_eq!(usage(&["--all", "-s", "vim"]), "--all works with kill, kill-session, send, --get, --link and --unlink");
+        assert_eq!(usage(&["--all", "-G", "-t", "4", "vim"]), "every match can't go at the same --index");
+        assert_eq!(usage(&["--dry-run", "vim"]), "--dry-run needs something to do, like --get or kill");
+        assert_eq!(usage(&["--dry-run", "save", "snapshot"]), "--dry-run doesn't work with save");
+        assert_eq!(usage(&["-t", "one", "-G", "vim"]), "invalid window index \"one\"");
+    }
 }
@@ -0,0 +1,333 @@
+//! A minimal terminal picker, for choosing between ambiguous matches.
+//!
+//! Draws a numbered list on the controlling terminal and lets the user move
+//! through it with the arrow keys (or `j`/`k`), choose with enter or by
+//! typing an entry's number, and give up with `q`, escape or ^C. A number
+//! is chosen as soon as it's typed, unless it could be the start of a
+//! longer one, in which case enter chooses it. It can ask a yes or no
+//! question too.
+
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+
+/// Whether standard output is a terminal, and so whether it's worth asking.
+pub fn is_interactive() -> bool {
+    unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    Enter,
+    Digit(usize),
+    Cancel,
+    Other,
+}
+
+/// Where the user is in the list: the entry highlighted, and the number
+/// they've typed so far, if any.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct State {
+    selected: usize,
+    typed: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Move(State),
+    Choose(usize),
+    Cancel,
+}
+
+/// Decode the next key from raw terminal input, returning it and how many
+/// bytes it took up.
+fn decode(input: &[u8]) -> (Key, usize) {
+    match input {
+        [0x1b, b'[', b'A', ..] => (Key::Up, 3),
+        [0x1b, b'[', b'B', ..] => (Key::Down, 3),
+        [0x1b, b'[', _, ..] => (Key::Other, 3),
+        [0x1b, ..] => (Key::Cancel, 1),
+        [b'k', ..] => (Key::Up, 1),
+        [b'j', ..] => (Key::Down, 1),
+        [b'\r', ..] | [b'\n', ..] => (Key::Enter, 1),
+        [b'q', ..] | [0x03, ..] => (Key::Cancel, 1),
+        [c @ b'0'..=b'9', ..] => (Key::Digit((c - b'0') as usize), 1),
+        _ => (Key::Other, 1),
+    }
+}
+
+/// What pressing `key` does in `state`, out of `len` entries. A digit which
+/// can't extend the number typed so far starts a new one.
+fn step(key: Key, state: State, len: usize) -> Step {
+    let moved = |selected| Step::Move(State { selected, typed: 0 });
+    match key {
+        Key::Up if state.selected > 0 => moved(state.selected - 1),
+        Key::Down if state.selected + 1 < len => moved(state.selected + 1),
+        Key::Enter => Step::Choose(state.selected),
+        Key::Digit(n) => {
+            let extended = state.typed * 10 + n;
+            let typed = if (1..=len).contains(&extended) {
+                extended
+            } else if (1..=len).contains(&n) {
+                n
+            } else {
+                return Step::Move(state);
+            };
+            if typed * 10 > len {
+                Step::Choose(typed - 1)
+            } else {
+                Step::Move(State { selected: typed - 1, typed })
+            }
+        }
+        Key::Cancel => Step::Cancel,
+        _ => Step::Move(state),
+    }
+}
+
+/// Puts the terminal into raw mode for as long as it's alive.
+struct RawMode {
+    fd: RawFd,
+    saved: libc::termios,
+}
+
+impl RawMode {
+    fn enable(tty: &File) -> io::Result<RawMode> {
+        let fd = tty.as_raw_fd();
+        unsafe {
+            let mut saved: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(fd, &mut saved) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut raw = saved;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(fd, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(RawMode { fd, saved })
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(self.fd, libc::TCSANOW, &self.saved);
+        }
+    }
+}
+
+fn draw<W: Write>(w: &mut W, prompt: &str, candidates: &[String], selected: usize) -> io::Result<()> {
+    write!(w, "{}\r\n", prompt)?;
+    for (i, candidate) in candidates.iter().enumerate() {
+        if i == selected {
+            write!(w, "\x1b[7m> {}) {}\x1b[0m\r\n", i + 1, candidate)?;
+        } else {
+            write!(w, "  {}) {}\r\n", i + 1, candidate)?;
+        }
+    }
+    w.flush()
+}
+
+/// Ask the user to choose one of `candidates` on the controlling terminal,
+/// returning the index of their choice, or `None` if they gave up.
+pub fn pick(prompt: &str, candidates: &[String]) -> io::Result<Option<usize>> {
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    let raw = RawMode::enable(&tty)?;
+    let lines = candidates.len() + 1;
+
+    let mut state = State::default();
+    let mut buf = [0; 16];
+    let choice = loop {
+        draw(&mut tty, prompt, candidates, state.selected)?;
+        let n = tty.read(&mut buf)?;
+        if n == 0 {
+            break None;
+        }
+
+        let mut input = &buf[..n];
+        let mut outcome = None;
+        while !input.is_empty() && outcome.is_none() {
+            let (key, used) = decode(input);
+            input = &input[used.min(input.len())..];
+            match step(key, state, candidates.len()) {
+                Step::Move(to) => state = to,
+                Step::Choose(i) => outcome = Some(Some(i)),
+                Step::Cancel => outcome = Some(None),
+            }
+        }
+
+        // Move back up over the list and clear it, ready to redraw or leave.
+        write!(tty, "\x1b[{}A\x1b[J", lines)?;
+        if let Some(choice) = outcome {
+            break choice;
+        }
+    };
+
+    drop(raw);
+    Ok(choice)
+}
+
+/// Whether an answer to a yes or no question means yes. Anything but a yes
+/// is a no.
+fn is_yes(answer: &str) -> bool {
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Ask the user a yes or no question on the controlling terminal, taking
+/// no for an answer unless they say otherwise.
+pub fn confirm(question: &str) -> io::Result<bool> {
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    write!(tty, "{} [y/N] ", question)?;
+    tty.flush()?;
+    let mut answer = String::new();
+    BufReader::new(tty).read_line(&mut answer)?;
+    Ok(is_yes(&answer))
+}
+
+/// Somewhere to ask the user things: `Terminal`, or `FakePrompt` in tests.
+pub trait Prompt {
+    /// Whether there's anyone to ask.
+    fn is_interactive(&self) -> bool;
+    /// Ask which of `candidates` the user meant, as `pick` does.
+    fn pick(&self, prompt: &str, candidates: &[String]) -> io::Result<Option<usize>>;
+    /// Ask a yes or no question, as `confirm` does.
+    fn confirm(&self, question: &str) -> io::Result<bool>;
+}
+
+/// Asks on the controlling terminal.
+#[derive(Debug, Default)]
+pub struct Terminal;
+
+impl Prompt for Terminal {
+    fn is_interactive(&self) -> bool {
+        is_interactive()
+    }
+
+    fn pick(&self, prompt: &str, candidates: &[String]) -> io::Result<Option<usize>> {
+        pick(prompt, candidates)
+    }
+
+    fn confirm(&self, question: &str) -> io::Result<bool> {
+        confirm(question)
+    }
+}
+
+/// A user who gives the same answers every time, and remembers what they
+/// were asked. Without any answers, there's no one there to ask.
+#[derive(Debug, Default)]
+pub struct FakePrompt {
+    choice: Option<Option<usize>>,
+    answer: Option<bool>,
+    asked: RefCell<Vec<String>>,
+}
+
+impl FakePrompt {
+    /// No one to ask.
+    pub fn new() -> FakePrompt {
+        FakePrompt::default()
+    }
+
+    /// Choose the candidate at `index` when asked to pick, or back out if
+    /// `None`.
+    pub fn choosing(mut self, index: Option<usize>) -> FakePrompt {
+        self.choice = Some(index);
+        self
+    }
+
+    /// Answer every yes or no question with `answer`.
+    pub fn answering(mut self, answer: bool) -> FakePrompt {
+        self.answer = Some(answer);
+        self
+    }
+
+    /// Every prompt and question asked so far, in order.
+    pub fn asked(&self) -> Vec<String> {
+        self.asked.borrow().clone()
+    }
+}
+
+impl Prompt for FakePrompt {
+    fn is_interactive(&self) -> bool {
+        self.choice.is_some() || self.answer.is_some()
+    }
+
+    fn pick(&self, prompt: &str, _candidates: &[String]) -> io::Result<Option<usize>> {
+        self.asked.borrow_mut().push(prompt.to_string());
+        Ok(self.choice.unwrap_or_default())
+    }
+
+    fn confirm(&self, question: &str) -> io::Result<bool> {
+        self.asked.borrow_mut().push(question.to_string());
+        Ok(self.answer.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(b"\x1b[A"), (Key::Up, 3));
+        assert_eq!(decode(b"\x1b[Bj"), (Key::Down, 3));
+        assert_eq!(decode(b"\x1b"), (Key::Cancel, 1));
+        assert_eq!(decode(b"\r"), (Key::Enter, 1));
+        assert_eq!(decode(b"7"), (Key::Digit(7), 1));
+        assert_eq!(decode(b"0"), (Key::Digit(0), 1));
+        assert_eq!(decode(b"x"), (Key::Other, 1));
+    }
+
+    fn at(selected: usize) -> State {
+        State { selected, typed: 0 }
+    }
+
+    #[test]
+    fn test_step() {
+        assert_eq!(step(Key::Up, at(0), 3), Step::Move(at(0)));
+        assert_eq!(step(Key::Down, at(0), 3), Step::Move(at(1)));
+        assert_eq!(step(Key::Down, at(2), 3), Step::Move(at(2)));
+        assert_eq!(step(Key::Enter, at(2), 3), Step::Choose(2));
+        assert_eq!(step(Key::Digit(3), at(0), 3), Step::Choose(2));
+        assert_eq!(step(Key::Digit(4), at(0), 3), Step::Move(at(0)));
+        assert_eq!(step(Key::Digit(0), at(1), 3), Step::Move(at(1)));
+        assert_eq!(step(Key::Cancel, at(1), 3), Step::Cancel);
+    }
+
+    #[test]
+    fn test_step_numbers() {
+        // With 12 entries, 1 might be the start of 10, 11 or 12.
+        let one = State { selected: 0, typed: 1 };
+        assert_eq!(step(Key::Digit(1), at(4), 12), Step::Move(one));
+        assert_eq!(step(Key::Enter, one, 12), Step::Choose(0));
+        assert_eq!(step(Key::Digit(2), one, 12), Step::Choose(11));
+        assert_eq!(step(Key::Digit(0), one, 12), Step::Choose(9));
+        assert_eq!(step(Key::Digit(3), at(0), 12), Step::Choose(2));
+        // 13 is too far, so the 3 starts again.
+        assert_eq!(step(Key::Digit(3), one, 12), Step::Choose(2));
+        assert_eq!(step(Key::Down, one, 12), Step::Move(at(1)));
+    }
+
+    #[test]
+    fn test_is_yes() {
+        assert!(is_yes("y\n"));
+        assert!(is_yes(" Yes "));
+        assert!(!is_yes("\n"));
+        assert!(!is_yes("yep"));
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut out = vec![];
+        let candidates = vec!["work:0: zsh".to_string(), "work:1: vim".to_string()];
+        draw(&mut out, "Which?", &candidates, 1).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Which?\r\n  1) work:0: zsh\r\n\x1b[7m> 2) work:1: vim\x1b[0m\r\n"
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! tmux commands worked out ahead of time, so that they can be shown
+//! instead of run, or run one by one with each outcome reported.
+
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::error::Error;
+use crate::tmux::{command_line, Server, TmuxBackend};
+
+/// A tmux command, and what it acts on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    /// What the command acts on, as the user would know it.
+    pub target: String,
+    pub server: Server,
+    pub args: Vec<String>,
+    /// Whether the command takes over the terminal, replacing tinfo, as
+    /// attaching does.
+    pub exec: bool,
+}
+
+impl Step {
+    pub fn new(target: &str, server: &Server, args: &[&str]) -> Step {
+        Step {
+            target: target.to_string(),
+            server: server.clone(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            exec: false,
+        }
+    }
+
+    /// A step which hands the terminal over to tmux, and so has to come
+    /// last.
+    pub fn exec(target: &str, server: &Server, args: &[&str]) -> Step {
+        Step {
+            exec: true,
+            ..Step::new(target, server, args)
+        }
+    }
+
+    pub fn run(&self, tmux: &dyn TmuxBackend) -> Result<(), Error> {
+        let args: Vec<&str> = self.args.iter().map(String::as_str).collect();
+        if self.exec {
+            tmux.exec(&self.server, &args)
+        } else {
+            tmux.run(&self.server, &args)
+        }
+    }
+}
+
+/// Quote `word` for a POSIX shell, if it needs it.
+pub(crate) fn quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./:%@=,+".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// The step's command line, ready to paste into a shell.
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let args: Vec<&str> = self.args.iter().map(String::as_str).collect();
+        let line: Vec<String> = command_line(&self.server, &args).iter().map(|word| quote(word)).collect();
+        write!(f, "{}", line.join(" "))
+    }
+}
+
+/// Run every step in turn, stopping at the first to fail.
+pub fn execute(tmux: &dyn TmuxBackend, plan: &[Step]) -> Result<(), Error> {
+    plan.iter().try_for_each(|step| step.run(tmux))
+}
+
+/// Run every step, carrying on past any that fail, and return how each
+/// one went.
+pub fn run_all<'a>(tmux: &dyn TmuxBackend, plan: &'a [Step]) -> Vec<(&'a Step, Result<(), Error>)> {
+    plan.iter().map(|step| (step, step.run(tmux))).collect()
+}
+
+/// Write a plan out as a shell script, with what each step is for.
+pub fn dump<W: Write>(w: &mut W, plan: &[Step]) -> io::Result<()> {
+    for step in plan {
+        writeln!(w, "{}  # {}", step, step.target)?;
+    }
+    Ok(())
+}
+
+/// Write a line per step saying how it went.
+pub fn dump_report<W: Write>(w: &mut W, outcomes: &[(&Step, Result<(), Error>)]) -> io::Result<()> {
+    for (step, outcome) in outcomes {
+        match outcome {
+            Ok(()) => writeln!(w, "ok      {}", step.target)?,
+            Err(e) => writeln!(w, "failed  {}: {}", step.target, e)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmux::FakeBackend;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("kill-window"), "kill-window");
+        assert_eq!(quote("$1:3"), "'$1:3'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+        assert_eq!(quote(""), "''");
+    }
+
+    #[test]
+    fn test_display() {
+        let step = Step::new("work:3: logs", &Server::Name("k1".to_string()), &["kill-window", "-t", "$0:3"]);
+        assert_eq!(step.to_string(), "tmux -u -L k1 kill-window -t '$0:3'");
+    }
+
+    #[test]
+    fn test_execute() {
+        let tmux = FakeBackend::new().fail("kill-window", "can't find window: 1");
+        let plan = vec![
+            Step::new("logs", &Server::Default, &["kill-window", "-t", "$0:1"]),
+            Step::exec("work", &Server::Default, &["attach-session", "-t", "$0"]),
+        ];
+        assert!(execute(&tmux, &plan).is_err());
+        assert_eq!(tmux.calls().len(), 1);
+    }
+
+    #[test]
+    fn test_run_all() {
+        let tmux = FakeBackend::new().fail("kill-session", "can't find session: $9");
+        let plan = vec![
+            Step::new("logs", &Server::Default, &["kill-window", "-t", "$0:1"]),
+            Step::new("old", &Server::Default, &["kill-session", "-t", "$9"]),
+        ];
+        let outcomes = run_all(&tmux, &plan);
+        assert_eq!(tmux.calls().len(), 2);
+
+        let mut out = vec![];
+        dump_report(&mut out, &outcomes).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "ok      logs\nfailed  old: tmux: can't find session: $9\n"
+        );
+
+        let mut out = vec![];
+        dump(&mut out, &plan[..1]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "tmux -u kill-window -t '$0:1'  # logs\n");
+    }
+}
@@ -0,0 +1,623 @@
+//! Sessions described in a file, brought up with `tinfo up`.
+//!
+//! A project file is TOML:
+//!
+//! ```toml
+//! name = "work"           # the session's name, if not the file's
+//! root = "~/src/work"     # where everything starts, unless told otherwise
+//!
+//! [env]                   # set in the session's environment
+//! RUST_LOG = "debug"
+//!
+//! [[window]]
+//! name = "edit"
+//! layout = "main-vertical"
+//! panes = ["vim", "cargo watch -x test"]
+//!
+//! [[window]]
+//! name = "logs"
+//! root = "/var/log"       # relative to the project's root, if relative
+//!
+//! [window.env]            # set for this window's panes alone
+//! LESS = "-R"
+//!
+//! [[window.pane]]
+//! command = ["cd nginx", "tail -f access.log"]
+//!
+//! [[window.pane]]
+//! split = "right"         # or "below", the default
+//! size = "30%"
+//! root = "syslog"
+//! ```
+//!
+//! A window's `command` is the command for its first pane, and `panes` a
+//! pane per command (an empty one leaves the shell alone), ahead of any
+//! `[[window.pane]]`. A window without any panes gets one, and a project
+//! without any windows gets one too.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+
+use crate::error::{Error, FileError, FileKind};
+use crate::tmux::{Server, TmuxBackend};
+use crate::{build_windowlist, create, expand_home, type_command, WindowList};
+
+/// Which way a pane is split off the one before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Split {
+    #[default]
+    Below,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaneSpec {
+    pub root: Option<String>,
+    /// Typed into the pane in order, once it has started.
+    #[serde(rename = "command", deserialize_with = "commands")]
+    pub commands: Vec<String>,
+    pub split: Split,
+    /// How big to make the pane, in lines or columns, or as a percentage.
+    #[serde(deserialize_with = "size")]
+    pub size: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(from = "WindowFile")]
+pub struct WindowSpec {
+    pub name: Option<String>,
+    pub root: Option<String>,
+    /// One of tmux's layout names, or a layout string it printed.
+    pub layout: Option<String>,
+    pub env: Vec<(String, String)>,
+    pub panes: Vec<PaneSpec>,
+}
+
+/// A `[[window]]` as it's written, with its shorthands for panes.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct WindowFile {
+    name: Option<String>,
+    root: Option<String>,
+    layout: Option<String>,
+    #[serde(deserialize_with = "variables")]
+    env: Vec<(String, String)>,
+    command: Option<CommandList>,
+    panes: Vec<CommandList>,
+    pane: Vec<PaneSpec>,
+}
+
+impl From<WindowFile> for WindowSpec {
+    fn from(file: WindowFile) -> WindowSpec {
+        let mut panes: Vec<PaneSpec> = file.panes
+            .into_iter()
+            .map(|CommandList(commands)| PaneSpec {
+                commands: commands.into_iter().filter(|command| !command.is_empty()).collect(),
+                ..PaneSpec::default()
+            })
+            .chain(file.pane)
+            .collect();
+        if panes.is_empty() {
+            panes.push(PaneSpec::default());
+        }
+        if let Some(CommandList(commands)) = file.command {
+            panes[0].commands = commands;
+        }
+        WindowSpec {
+            name: file.name,
+            root: file.root,
+            layout: file.layout,
+            env: file.env,
+            panes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Project {
+    /// The name of the session to bring up.
+    #[serde(deserialize_with = "name")]
+    pub name: String,
+    pub root: Option<String>,
+    #[serde(deserialize_with = "variables")]
+    pub env: Vec<(String, String)>,
+    #[serde(rename = "window")]
+    pub windows: Vec<WindowSpec>,
+}
+
+/// A string, or a number standing in for one.
+struct Text;
+
+impl<'de> Visitor<'de> for Text {
+    type Value = String;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a string")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<String, E> {
+        Ok(value.to_string())
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<String, E> {
+        Ok(value.to_string())
+    }
+}
+
+/// A command, or a list of them.
+struct Commands;
+
+impl<'de> Visitor<'de> for Commands {
+    type Value = Vec<String>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a command or a list of them")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Vec<String>, E> {
+        Ok(vec![value.to_string()])
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<String>, A::Error> {
+        let mut commands = vec![];
+        while let Some(command) = seq.next_element()? {
+            commands.push(command);
+        }
+        Ok(commands)
+    }
+}
+
+/// What `Text` and `Commands` read, where a type is needed for one.
+struct TextValue(String);
+
+impl<'de> Deserialize<'de> for TextValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<TextValue, D::Error> {
+        deserializer.deserialize_any(Text).map(TextValue)
+    }
+}
+
+struct CommandList(Vec<String>);
+
+impl<'de> Deserialize<'de> for CommandList {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<CommandList, D::Error> {
+        deserializer.deserialize_any(Commands).map(CommandList)
+    }
+}
+
+fn commands<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    deserializer.deserialize_any(Commands)
+}
+
+fn size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    deserializer.deserialize_any(Text).map(Some)
+}
+
+fn name<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    if name.is_empty() {
+        return Err(de::Error::custom("the session needs a name"));
+    }
+    Ok(name)
+}
+
+/// A table of environment variables, kept in the order they're written.
+fn variables<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, String)>, D::Error> {
+    struct Env;
+
+    impl<'de> Visitor<'de> for Env {
+        type Value = Vec<(String, String)>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a table of environment variables")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut env = vec![];
+            while let Some(key) = map.next_key()? {
+                env.push((key, map.next_value::<TextValue>()?.0));
+            }
+            Ok(env)
+        }
+    }
+
+    deserializer.deserialize_map(Env)
+}
+
+impl Project {
+    /// Read a project file. `name` is the session's name unless the file
+    /// names it.
+    pub fn parse(name: &str, text: &str) -> Result<Project, FileError> {
+        let mut project: Project = toml::from_str(text).map_err(|e| {
+            let line = e.span().map_or(1, |span| text[..span.start].matches('\n').count() + 1);
+            FileError::new(FileKind::Project, line, e.message())
+        })?;
+        if project.name.is_empty() {
+            if name.is_empty() {
+                return Err(FileError::new(FileKind::Project, 1, "the session needs a name"));
+            }
+            project.name = name.to_string();
+        }
+        if project.windows.is_empty() {
+            project.windows.push(WindowSpec::from(WindowFile::default()));
+        }
+        Ok(project)
+    }
+
+    /// Read the project file at `path`, whose name is the session's unless
+    /// it says otherwise. A relative root is taken from where we are now.
+    pub fn load(path: &Path) -> Result<Project, Error> {
+        let text = fs::read_to_string(path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::NoProject(path.to_path_buf()),
+            _ => Error::Io(e),
+        })?;
+        let name = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+        let mut project = Project::parse(&name, &text)?;
+        if let Some(root) = project.root.take() {
+            project.root = Some(env::current_dir()?.join(expand_home(&root)).display().to_string());
+        }
+        Ok(project)
+    }
+
+    /// Where a pane starts: its own root, relative to its window's, relative
+    /// to the project's. `None` leaves it up to tmux.
+    fn directory(&self, window: &WindowSpec, pane: &PaneSpec) -> Option<String> {
+        let mut dir: Option<PathBuf> = None;
+        for root in [&self.root, &window.root, &pane.root].iter().filter_map(|root| root.as_ref()) {
+            let root = PathBuf::from(expand_home(root));
+            dir = Some(match dir {
+                Some(dir) => dir.join(root),
+                None => root,
+            });
+        }
+        dir.map(|dir| dir.display().to_string())
+    }
+}
+
+/// Where the project called `name` lives: `name` itself if it looks like a
+/// path, otherwise `$XDG_CONFIG_HOME/tinfo/projects/<name>.toml`.
+pub fn find(name: &str) -> PathBuf {
+    if name.contains('/') || name.ends_with(".toml") {
+        return PathBuf::from(name);
+    }
+    let config = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(expand_home("~/.config")),
+    };
+    config.join("tinfo").join("projects").join(format!("{}.toml", name))
+}
+
+fn env_args(env: &[(String, String)]) -> Vec<String> {
+    env.iter()
+        .flat_map(|(key, value)| vec!["-e".to_string(), format!("{}={}", key, value)])
+        .collect()
+}
+
+/// The name tmux gives a session asked to be called `name`, which can't
+/// have `.` or `:` in it since they mean something in a target.
+fn session_name(name: &str) -> String {
+    name.replace(['.', ':'], "_")
+}
+
+/// Build `project`'s session, returning its id. If anything goes wrong
+/// partway through, what was built is killed off again, rather than left
+/// for the next `up` to take for the whole session.
+fn create_session(tmux: &dyn TmuxBackend, server: &Server, project: &Project) -> Result<String, Error> {
+    let mut session_id = None;
+    match (build_session(tmux, server, project, &mut session_id), session_id) {
+        (Ok(()), Some(id)) => Ok(id),
+        (Ok(()), None) => Err(Error::NoMatch),
+        (Err(e), Some(id)) => {
+            let _ = tmux.run(server, &["kill-session", "-t", &id]);
+            Err(e)
+        }
+        (Err(e), None) => Err(e),
+    }
+}
+
+fn build_session(
+    tmux: &dyn TmuxBackend,
+    server: &Server,
+    project: &Project,
+    session_id: &mut Option<String>,
+) -> Result<(), Error> {
+    for window in project.windows.iter() {
+        let first = match window.panes.first() {
+            Some(first) => first,
+            None => continue,
+        };
+        let mut args: Vec<String> = vec![];
+        if let Some(name) = &window.name {
+            args.extend(vec!["-n".into(), name.clone()]);
+        }
+        if let Some(dir) = project.directory(window, first) {
+            args.extend(vec!["-c".into(), dir]);
+        }
+
+        let created = match *session_id {
+            None => {
+                let mut new_session: Vec<String> = vec!["new-session".into(), "-d".into(), "-P".into()];
+                new_session.extend(vec!["-s".into(), project.name.clone()]);
+                new_session.extend(args);
+                new_session.extend(env_args(&project.env));
+                let new_session: Vec<&str> = new_session.iter().map(String::as_str).collect();
+                let created = create(tmux, server, &new_session)?;
+
+                // The session's first pane is already running, so start it
+                // again to give it the window's environment.
+                if !window.env.is_empty() {
+                    let mut respawn = vec!["respawn-pane".to_string(), "-k".into(), "-t".into()];
+                    respawn.push(created.pane.id.clone());
+                    if let Some(dir) = project.directory(window, first) {
+                        respawn.extend(vec!["-c".into(), dir]);
+                    }
+                    respawn.extend(env_args(&window.env));
+                    let respawn: Vec<&str> = respawn.iter().map(String::as_str).collect();
+                    tmux.run(server, &respawn)?;
+                }
+                *session_id = Some(created.session_id.clone());
+                created
+            }
+            Some(ref id) => {
+                let mut new_window: Vec<String> = vec!["new-window".into(), "-d".into(), "-P".into()];
+                new_window.extend(vec!["-t".into(), format!("{}:", id)]);
+                new_window.extend(args);
+                new_window.extend(env_args(&window.env));
+                let new_window: Vec<&str> = new_window.iter().map(String::as_str).collect();
+                create(tmux, server, &new_window)?
+            }
+        };
+
+        let mut panes = vec![(first, created.pane.id)];
+        for pane in window.panes.iter().skip(1) {
+            let last = panes[panes.len() - 1].1.clone();
+            let mut split = vec!["split-window".to_string(), "-d".into(), "-P".into(), "-t".into(), last];
+            if pane.split == Split::Right {
+                split.push("-h".into());
+            }
+            if let Some(size) = &pane.size {
+                split.extend(vec!["-l".into(), size.clone()]);
+            }
+            if let Some(dir) = project.directory(window, pane) {
+                split.extend(vec!["-c".into(), dir]);
+            }
+            split.extend(env_args(&window.env));
+            let split: Vec<&str> = split.iter().map(String::as_str).collect();
+            panes.push((pane, create(tmux, server, &split)?.pane.id));
+        }
+
+        let target = format!("{}:{}", created.session_id, created.window_index);
+        if let Some(layout) = &window.layout {
+            tmux.run(server, &["select-layout", "-t", &target, layout])?;
+        }
+        for (pane, id) in panes.iter() {
+            for command in pane.commands.iter() {
+                type_command(tmux, server, id, command)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Bring `project` up on `server`, unless its session is already there.
+/// Either way, returns the session, ready to attach to.
+pub fn up(tmux: &dyn TmuxBackend, server: &Server, project: &Project) -> Result<WindowList, Error> {
+    let name = session_name(&project.name);
+    let created = match tmux.run(server, &["has-session", "-t", &format!("={}", name)]) {
+        Ok(()) => None,
+        Err(Error::NoServer(_)) | Err(Error::Tmux(_)) => Some(create_session(tmux, server, project)?),
+        Err(e) => return Err(e),
+    };
+
+    let session: HashMap<_, _> = build_windowlist(tmux, server)?
+        .into_iter()
+        .filter(|(session, _)| match created {
+            Some(ref id) => &session.id == id,
+            None => session.name == name,
+        })
+        .collect();
+    if session.is_empty() {
+        return Err(Error::NoMatch);
+    }
+    Ok(session)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{PaneRecord, Record};
+    use crate::testing::{calls_since, created, line};
+    use crate::tmux::FakeBackend;
+
+    const PROJECT: &str = r#"
+# Everything for work.
+root = "/src/work"
+
+[env]
+RUST_LOG = "debug"   # noisy
+
+[[window]]
+name = "edit"
+layout = "main-vertical"
+panes = [
+    "vim",
+    "",
+]
+
+[[window]]
+name = "logs"
+root = "/var/log"
+
+[window.env]
+LESS = '-R'
+
+[[window.pane]]
+command = ["cd nginx", "tail -f \"access.log\""]
+
+[[window.pane]]
+split = "right"
+size = 30
+root = "syslog"
+"#;
+
+    #[test]
+    fn test_parse() {
+        let project = Project::parse("work", PROJECT).unwrap();
+        assert_eq!(project.name, "work");
+        assert_eq!(project.env, vec![("RUST_LOG".to_string(), "debug".to_string())]);
+        assert_eq!(project.windows.len(), 2);
+
+        let edit = &project.windows[0];
+        assert_eq!(edit.layout.as_deref(), Some("main-vertical"));
+        assert_eq!(edit.panes.len(), 2);
+        assert_eq!(edit.panes[0].commands, vec!["vim"]);
+        assert!(edit.panes[1].commands.is_empty());
+
+        let logs = &project.windows[1];
+        assert_eq!(logs.env, vec![("LESS".to_string(), "-R".to_string())]);
+        assert_eq!(logs.panes[0].commands, vec!["cd nginx", "tail -f \"access.log\""]);
+        assert_eq!(logs.panes[1].split, Split::Right);
+        assert_eq!(logs.panes[1].size.as_deref(), Some("30"));
+        assert_eq!(project.directory(logs, &logs.panes[1]).as_deref(), Some("/var/log/syslog"));
+        assert_eq!(project.directory(edit, &edit.panes[0]).as_deref(), Some("/src/work"));
+    }
+
+    #[test]
+    fn test_parse_defaults() {
+        let project = Project::parse("scratch", "").unwrap();
+        assert_eq!(project.name, "scratch");
+        assert_eq!(project.windows, vec![WindowSpec {
+            panes: vec![PaneSpec::default()],
+            ..WindowSpec::default()
+        }]);
+        let project = Project::parse("scratch", "name = 'other'\n[[window]]\ncommand = 'htop'").unwrap();
+        assert_eq!(project.name, "other");
+        assert_eq!(project.windows[0].panes[0].commands, vec!["htop"]);
+    }
+
+    #[test]
+    fn test_parse_toml() {
+        let project = Project::parse("work", r#"
+env = { B = "1", A = 2 }
+window = [{ name = "caf\u00e9", command = '''vim "a b"''', env.LESS = "-R" }]
+"#).unwrap();
+        assert_eq!(project.env, vec![
+            ("B".to_string(), "1".to_string()),
+            ("A".to_string(), "2".to_string()),
+        ]);
+        let window = &project.windows[0];
+        assert_eq!(window.name.as_deref(), Some("café"));
+        assert_eq!(window.panes[0].commands, vec!["vim \"a b\""]);
+        assert_eq!(window.env, vec![("LESS".to_string(), "-R".to_string())]);
+
+        let project = Project::parse("work", r#"
+env.EDITOR = "vim"
+
+[[window]]
+command = """
+htop"""
+pane = [{ split = "right", size = "30%" }]
+"#).unwrap();
+        assert_eq!(project.env, vec![("EDITOR".to_string(), "vim".to_string())]);
+        let panes = &project.windows[0].panes;
+        assert_eq!(panes[0].commands, vec!["htop"]);
+        assert_eq!((panes[0].split, panes[0].size.as_deref()), (Split::Right, Some("30%")));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |text| Project::parse("work", text).unwrap_err();
+        assert_eq!(
+            error("\nroot = \"/src\n"),
+            FileError::new(FileKind::Project, 2, "invalid basic string, expected `\"`")
+        );
+        assert_eq!(
+            error("colour = 'red'").message,
+            "unknown field `colour`, expected one of `name`, `root`, `env`, `window`"
+        );
+        assert_eq!(error("[[window.pane]]").message, "invalid type: map, expected a sequence");
+        assert_eq!(error("[[window]]\nname = ['a']"), FileError::new(FileKind::Project, 2, "invalid type: sequence, expected a string"));
+        assert_eq!(
+            error("[[window]]\n[[window.pane]]\nsplit = 'up'").message,
+            "unknown variant `up`, expected `below` or `right`"
+        );
+        assert_eq!(error("name = ''").message, "the session needs a name");
+        assert_eq!(Project::parse("", "").unwrap_err().message, "the session needs a name");
+    }
+
+    #[test]
+    fn test_create_session() {
+        let tmux = FakeBackend::new()
+            .respond("new-session", &created("$4", "0", "%1"))
+            .respond("new-window", &created("$4", "1", "%3"))
+            .respond("split-window", &created("$4", "1", "%4"));
+        let project = Project::parse("work", PROJECT).unwrap();
+        create_session(&tmux, &Server::Default, &project).unwrap();
+
+        let calls = calls_since(&tmux, 0);
+        let format = PaneRecord::format();
+        assert_eq!(calls, vec![
+            format!("new-session -F {} -d -P -s work -n edit -c /src/work -e RUST_LOG=debug", format),
+            format!("split-window -F {} -d -P -t %1 -c /src/work", format),
+            "select-layout -t $4:0 main-vertical".to_string(),
+            "send-keys -t %1 -l vim ; send-keys -t %1 Enter".to_string(),
+            format!("new-window -F {} -d -P -t $4: -n logs -c /var/log -e LESS=-R", format),
+            format!("split-window -F {} -d -P -t %3 -h -l 30 -c /var/log/syslog -e LESS=-R", format),
+            "send-keys -t %3 -l cd nginx ; send-keys -t %3 Enter".to_string(),
+            "send-keys -t %3 -l tail -f \"access.log\" ; send-keys -t %3 Enter".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn test_create_session_window_env() {
+        let tmux = FakeBackend::new().respond("new-session", &created("$4", "0", "%1"));
+        let project = Project::parse("work", "[[window]]\n[window.env]\nA = 'b'").unwrap();
+        create_session(&tmux, &Server::Default, &project).unwrap();
+        assert_eq!(tmux.calls()[1], vec!["respawn-pane", "-k", "-t", "%1", "-e", "A=b"]);
+    }
+
+    #[test]
+    fn test_create_session_failure() {
+        let tmux = FakeBackend::new()
+            .respond("new-session", &created("$4", "0", "%1"))
+            .respond("split-window", &created("$4", "0", "%2"))
+            .fail("select-layout", "invalid layout: main-vertica");
+        let project = Project::parse("work", PROJECT).unwrap();
+        assert!(create_session(&tmux, &Server::Default, &project).is_err());
+        assert_eq!(tmux.calls().last().unwrap(), &vec!["kill-session", "-t", "$4"]);
+    }
+
+    #[test]
+    fn test_up_existing() {
+        let session = line(&["$2", "work", "1", "0", "0", "0", ""]);
+        let tmux = FakeBackend::new().respond("list-sessions", &session);
+        let project = Project::parse("work", PROJECT).unwrap();
+        let windows = up(&tmux, &Server::Default, &project).unwrap();
+        assert_eq!(windows.len(), 1);
+        assert_eq!(tmux.calls()[0], vec!["has-session", "-t", "=work"]);
+        assert!(tmux.calls().iter().all(|call| !call[0].starts_with("new-")));
+    }
+
+    #[test]
+    fn test_up_renamed() {
+        // tmux won't have a '.' in a session name, so calls it my_app.
+        let session = line(&["$4", "my_app", "1", "0", "0", "0", ""]);
+        let tmux = FakeBackend::new()
+            .fail("has-session", "can't find session: my_app")
+            .respond("new-session", &created("$4", "0", "%1"))
+            .respond("list-sessions", &session);
+        let project = Project::parse("my.app", "").unwrap();
+        let windows = up(&tmux, &Server::Default, &project).unwrap();
+        assert_eq!(windows.keys().next().unwrap().name, "my_app");
+        assert_eq!(tmux.calls()[0], vec!["has-session", "-t", "=my_app"]);
+    }
+}
@@ -0,0 +1,364 @@
+//! Deciding which windows a search picks out.
+
+use std::fmt;
+
+use failure::Fail;
+use regex::{Regex, RegexBuilder};
+
+use crate::{expand_home, Pane, Tab};
+
+/// How a search term is compared against a window's name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchMode {
+    /// The term appears somewhere in the name.
+    #[default]
+    Substring,
+    /// The term is the whole name.
+    Exact,
+    /// The term is a regular expression found somewhere in the name.
+    Regex,
+    /// The term is a shell glob matching the whole name.
+    Glob,
+    /// The term's characters appear in order somewhere in the name, scored
+    /// by how closely together they turn up.
+    Fuzzy,
+}
+
+#[derive(Debug)]
+pub enum PatternError {
+    Regex(regex::Error),
+    Glob(String),
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatternError::Regex(e) => write!(f, "invalid regex: {}", e),
+            PatternError::Glob(glob) => write!(f, "invalid glob: unclosed `[` in {:?}", glob),
+        }
+    }
+}
+
+impl Fail for PatternError {}
+
+/// A compiled search term.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Substring { term: String, ignore_case: bool },
+    Exact { term: String, ignore_case: bool },
+    Regex(Regex),
+    Fuzzy { term: Vec<char>, ignore_case: bool },
+}
+
+impl Pattern {
+    pub fn new(term: &str, mode: MatchMode, ignore_case: bool) -> Result<Pattern, PatternError> {
+        let folded = if ignore_case { term.to_lowercase() } else { term.to_string() };
+        let regex = match mode {
+            MatchMode::Substring => return Ok(Pattern::Substring { term: folded, ignore_case }),
+            MatchMode::Exact => return Ok(Pattern::Exact { term: folded, ignore_case }),
+            MatchMode::Fuzzy => {
+                // Smart case, as in fzf: an all lowercase term ignores case.
+                let ignore_case = ignore_case || !term.chars().any(char::is_uppercase);
+                let term = if ignore_case { term.to_lowercase() } else { term.to_string() };
+                return Ok(Pattern::Fuzzy { term: term.chars().collect(), ignore_case });
+            }
+            MatchMode::Regex => term.to_string(),
+            MatchMode::Glob => glob_to_regex(term)?,
+        };
+        RegexBuilder::new(&regex)
+            .case_insensitive(ignore_case)
+            .build()
+            .map(Pattern::Regex)
+            .map_err(PatternError::Regex)
+    }
+
+    /// A case sensitive substring pattern, which can't fail to compile.
+    pub fn substring(term: &str) -> Pattern {
+        Pattern::Substring {
+            term: term.to_string(),
+            ignore_case: false,
+        }
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.score(text).is_some()
+    }
+
+    /// How well `text` matches, higher being better, or `None` if it
+    /// doesn't match at all. Only fuzzy patterns score anything but zero.
+    pub fn score(&self, text: &str) -> Option<i64> {
+        let matched = match self {
+            Pattern::Fuzzy { term, ignore_case } => return fuzzy_score(term, text, *ignore_case),
+            Pattern::Substring { term, ignore_case: false } => text.contains(term.as_str()),
+            Pattern::Substring { term, ignore_case: true } => {
+                text.to_lowercase().contains(term.as_str())
+            }
+            Pattern::Exact { term, ignore_case: false } => text == term,
+            Pattern::Exact { term, ignore_case: true } => text.to_lowercase() == *term,
+            Pattern::Regex(regex) => regex.is_match(text),
+        };
+        if matched { Some(0) } else { None }
+    }
+}
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_CONSECUTIVE: i64 = 4;
+const BONUS_FIRST_CHAR: i64 = 8;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP_EXTENSION: i64 = 1;
+
+/// Score `text` against the fuzzy pattern `term`, in the spirit of fzf's v1
+/// algorithm: find the first place the term appears as a subsequence, shrink
+/// that to the shortest span ending in the same place, then reward matches
+/// that are consecutive or fall on word boundaries and penalise gaps.
+fn fuzzy_score(term: &[char], text: &str, ignore_case: bool) -> Option<i64> {
+    let text: Vec<char> = if ignore_case {
+        text.to_lowercase().chars().collect()
+    } else {
+        text.chars().collect()
+    };
+    if term.is_empty() {
+        return Some(0);
+    }
+
+    // Forward scan for the end of the first occurrence.
+    let mut t = 0;
+    let mut end = None;
+    for (i, c) in text.iter().enumerate() {
+        if *c == term[t] {
+            t += 1;
+            if t == term.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    // Backward scan for the latest start of an occurrence ending there.
+    let mut t = term.len();
+    let mut start = end;
+    for i in (0..=end).rev() {
+        if text[i] == term[t - 1] {
+            t -= 1;
+            if t == 0 {
+                start = i;
+                break;
+            }
+        }
+    }
+
+    let mut score = 0;
+    let mut t = 0;
+    let mut last: Option<usize> = None;
+    for i in start..=end {
+        if t == term.len() || text[i] != term[t] {
+            continue;
+        }
+        score += SCORE_MATCH;
+        if i == 0 {
+            score += BONUS_FIRST_CHAR;
+        }
+        if i == 0 || is_boundary(text[i - 1]) {
+            score += BONUS_BOUNDARY;
+        }
+        match last {
+            Some(prev) if prev + 1 == i => score += BONUS_CONSECUTIVE,
+            Some(prev) => {
+                score -= PENALTY_GAP_START + PENALTY_GAP_EXTENSION * (i - prev - 2) as i64;
+            }
+            None => {}
+        }
+        last = Some(i);
+        t += 1;
+    }
+    Some(score)
+}
+
+fn is_boundary(c: char) -> bool {
+    c.is_whitespace() || "-_/.:".contains(c)
+}
+
+/// Translate a shell glob into an anchored regex.
+fn glob_to_regex(glob: &str) -> Result<String, PatternError> {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                regex.push('[');
+                let mut first = true;
+                loop {
+                    match chars.next() {
+                        Some('!') if first => {
+                            regex.push('^');
+                            continue;
+                        }
+                        Some(']') if !first => break,
+                        Some(c @ ('\\' | '[' | ']' | '&' | '~' | '^')) => {
+                            regex.push('\\');
+                            regex.push(c);
+                        }
+                        Some(c) => regex.push(c),
+                        None => return Err(PatternError::Glob(glob.to_string())),
+                    }
+                    first = false;
+                }
+                regex.push(']');
+            }
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push('$');
+    Ok(regex)
+}
+
+/// What `WindowSearch::select_tabs` looks for. A window matches when it
+/// satisfies every selector that's been given.
+#[derive(Debug, Clone, Default)]
+pub struct Search {
+    /// The window's name.
+    pub name: Option<Pattern>,
+    /// Part of the command running in one of the window's panes.
+    pub command: Option<String>,
+    /// Part of the working directory of one of the window's panes.
+    pub path: Option<String>,
+}
+
+impl Search {
+    /// A search for windows whose names contain `term`.
+    pub fn name(term: &str) -> Search {
+        Search {
+            name: Some(Pattern::substring(term)),
+            ..Search::default()
+        }
+    }
+
+    /// Whether no selectors have been given at all.
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.command.is_none() && self.path.is_none()
+    }
+
+    /// Whether any selectors look inside panes.
+    pub fn has_pane_selectors(&self) -> bool {
+        self.command.is_some() || self.path.is_some()
+    }
+
+    /// The panes of `tab` which satisfy every pane selector; all of them if
+    /// there aren't any.
+    pub fn panes<'a>(&self, tab: &'a Tab) -> Vec<&'a Pane> {
+        let path = self.path.as_ref().map(|path| expand_home(path));
+        tab.panes.iter()
+            .filter(|pane| match self.command {
+                Some(ref command) => pane.command.contains(command.as_str()),
+                None => true,
+            })
+            .filter(|pane| match path {
+                Some(ref path) => pane.path.contains(path.as_str()),
+                None => true,
+            })
+            .collect()
+    }
+
+    /// A copy of `tab` with only the panes which matched.
+    pub fn narrow(&self, tab: &Tab) -> Tab {
+        let panes = self.panes(tab).into_iter().cloned().collect();
+        Tab { panes, ..tab.clone() }
+    }
+
+    pub fn matches(&self, tab: &Tab) -> bool {
+        self.score(tab).is_some()
+    }
+
+    /// How well `tab` matches, as scored by the name pattern, or `None` if
+    /// it doesn't match every selector. Pane selectors need a single pane
+    /// to satisfy all of them.
+    pub fn score(&self, tab: &Tab) -> Option<i64> {
+        let score = match self.name {
+            Some(ref name) => name.score(&tab.name)?,
+            None => 0,
+        };
+        if self.has_pane_selectors() && self.panes(tab).is_empty() {
+            return None;
+        }
+        Some(score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(term: &str, mode: MatchMode, ignore_case: bool, text: &str) -> bool {
+        Pattern::new(term, mode, ignore_case).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn test_substring() {
+        assert!(matches("vim", MatchMode::Substring, false, "nvim"));
+        assert!(!matches("vim", MatchMode::Substring, false, "VIM"));
+        assert!(matches("vim", MatchMode::Substring, true, "NVIM"));
+        assert!(matches("VIM", MatchMode::Substring, true, "nvim"));
+    }
+
+    #[test]
+    fn test_exact() {
+        assert!(matches("vim", MatchMode::Exact, false, "vim"));
+        assert!(!matches("vim", MatchMode::Exact, false, "nvim"));
+        assert!(matches("Vim", MatchMode::Exact, true, "vIM"));
+    }
+
+    #[test]
+    fn test_regex() {
+        assert!(matches("^n?vim$", MatchMode::Regex, false, "nvim"));
+        assert!(!matches("^n?vim$", MatchMode::Regex, false, "gvim"));
+        assert!(matches("^VIM", MatchMode::Regex, true, "vim main.rs"));
+        match Pattern::new("(vim", MatchMode::Regex, false) {
+            Err(PatternError::Regex(_)) => {}
+            other => panic!("expected a regex error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(matches("*.rs", MatchMode::Glob, false, "main.rs"));
+        assert!(!matches("*.rs", MatchMode::Glob, false, "main.rs~"));
+        assert!(matches("log?", MatchMode::Glob, false, "logs"));
+        assert!(matches("[!a-c]pi", MatchMode::Glob, false, "zpi"));
+        assert!(!matches("[!a-c]pi", MatchMode::Glob, false, "api"));
+        assert!(matches("(a)+", MatchMode::Glob, false, "(a)+"));
+        assert!(matches("API*", MatchMode::Glob, true, "api-dev"));
+        match Pattern::new("[abc", MatchMode::Glob, false) {
+            Err(PatternError::Glob(glob)) => assert_eq!(glob, "[abc"),
+            other => panic!("expected a glob error, got {:?}", other),
+        }
+    }
+
+    fn fuzzy(term: &str, text: &str) -> Option<i64> {
+        Pattern::new(term, MatchMode::Fuzzy, false).unwrap().score(text)
+    }
+
+    #[test]
+    fn test_fuzzy() {
+        assert!(fuzzy("vmr", "vim main.rs").is_some());
+        assert!(fuzzy("rmv", "vim main.rs").is_none());
+        assert!(fuzzy("", "anything").is_some());
+
+        // Smart case: lowercase terms ignore case, others don't.
+        assert!(fuzzy("api", "API-dev").is_some());
+        assert!(fuzzy("Api", "api-dev").is_none());
+    }
+
+    #[test]
+    fn test_fuzzy_ranking() {
+        // Consecutive beats scattered.
+        assert!(fuzzy("log", "logs") > fuzzy("log", "lxoxg"));
+        // Word boundaries beat the middle of words.
+        assert!(fuzzy("md", "my-docs") > fuzzy("md", "amid"));
+        // The tightest occurrence is the one that's scored.
+        assert_eq!(fuzzy("ab", "axxab"), fuzzy("ab", "xab"));
+    }
+}
@@ -0,0 +1,450 @@
+//! Saving sessions to a file, and restoring them from one later.
+//!
+//! A snapshot is plain text, a line per session, window and pane, each of
+//! which belongs to the last line of the level above it (with its tabs
+//! shown as spaces here):
+//!
+//! ```text
+//! tinfo-snapshot  1
+//! session  work
+//! window  1  vim  80  24  a3f1,80x24,0,0[80x12,0,0,1,80x11,0,13,2]  1
+//! pane  0  1  /src/tinfo  vim
+//! pane  1  0  /src/tinfo  zsh
+//! ```
+//!
+//! Fields are separated by tabs, with tabs, newlines and backslashes inside
+//! them escaped as `\t`, `\n` and `\\`. Windows are the index, name, width,
+//! height, layout and whether the window is the session's current one;
+//! panes are the index, whether the pane is active, the working directory
+//! and the command line running in it, quoted for a shell.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::{Error, FileError, FileKind};
+use crate::plan::quote;
+use crate::sort::Sort;
+use crate::tmux::{Server, TmuxBackend};
+use crate::{build_windowlist, create, type_command, Listing, Pane, Session, Tab, Window, WindowList};
+
+const HEADER: &str = "tinfo-snapshot";
+const VERSION: &str = "1";
+
+fn escape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('t')) => out.push('\t'),
+            ('\\', Some('n')) => out.push('\n'),
+            ('\\', Some('\\')) => out.push('\\'),
+            (c, _) => {
+                out.push(c);
+                continue;
+            }
+        }
+        chars.next();
+    }
+    out
+}
+
+fn write_line<W: Write>(w: &mut W, fields: &[&str]) -> io::Result<()> {
+    let fields: Vec<_> = fields.iter().map(|field| escape(field)).collect();
+    writeln!(w, "{}", fields.join("\t"))
+}
+
+fn flag(value: bool) -> &'static str {
+    if value { "1" } else { "0" }
+}
+
+/// The id of the process group in the foreground of a process's terminal,
+/// from the process's `/proc/PID/stat`.
+fn foreground_pid(stat: &str) -> Option<u32> {
+    // The command name comes second, in parentheses, and may hold anything,
+    // spaces and parentheses included; tpgid is the fifth field after it.
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let tpgid: i64 = rest.split_whitespace().nth(5)?.parse().ok()?;
+    if tpgid > 0 { Some(tpgid as u32) } else { None }
+}
+
+/// A command line, quoted for a shell, from the NUL separated arguments in
+/// a process's `/proc/PID/cmdline`.
+fn quote_cmdline(cmdline: &[u8]) -> Option<String> {
+    let cmdline = String::from_utf8_lossy(cmdline);
+    let args: Vec<String> = cmdline.trim_end_matches('\0').split('\0').map(quote).collect();
+    if cmdline.is_empty() { None } else { Some(args.join(" ")) }
+}
+
+fn ps(field: &str, pid: &str) -> Option<String> {
+    let output = Command::new("ps").args(["-o", field, "-p", pid]).output().ok()?;
+    let value = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    if output.status.success() && !value.is_empty() { Some(value) } else { None }
+}
+
+/// The whole command line of the process in the foreground of the pane
+/// whose first process is `pid`, such as `tail -f syslog`. tmux only knows
+/// its name, which run without its arguments is usually no use at all.
+/// Without `/proc` this asks `ps`, which can't say where one argument ends
+/// and the next begins.
+fn foreground_command(pid: u32) -> Option<String> {
+    match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        Ok(stat) => {
+            let foreground = foreground_pid(&stat)?;
+            quote_cmdline(&fs::read(format!("/proc/{}/cmdline", foreground)).ok()?)
+        }
+        Err(_) => ps("args=", &ps("tpgid=", &pid.to_string())?),
+    }
+}
+
+/// Every session on `server`, ready to write as a snapshot. Each pane's
+/// command is the whole command line running in it, or nothing if that
+/// can't be found out, so that restoring never runs a command without its
+/// arguments.
+pub fn save(tmux: &dyn TmuxBackend, server: &Server) -> Result<WindowList, Error> {
+    let mut windows = build_windowlist(tmux, server)?;
+    for window in windows.values_mut() {
+        for pane in window.tabs.iter_mut().flat_map(|tab| tab.panes.iter_mut()) {
+            pane.command = foreground_command(pane.pid).unwrap_or_default();
+        }
+    }
+    Ok(windows)
+}
+
+/// Write a snapshot of `windows`.
+pub fn write<W: Write>(w: &mut W, windows: &WindowList) -> io::Result<()> {
+    write_line(w, &[HEADER, VERSION])?;
+    for (session, window) in Listing::new(windows, &Sort::default()).0 {
+        write_line(w, &["session", &session.name])?;
+        for tab in window.tabs.iter() {
+            write_line(w, &[
+                "window",
+                &tab.number.to_string(),
+                &tab.name,
+                &tab.width.to_string(),
+                &tab.height.to_string(),
+                &tab.layout,
+                flag(tab.flags.active),
+            ])?;
+            for pane in tab.panes.iter() {
+                write_line(w, &[
+                    "pane",
+                    &pane.index.to_string(),
+                    flag(pane.active),
+                    &pane.path,
+                    &pane.command,
+                ])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Read a snapshot back in. The sessions have no ids, since they don't
+/// exist yet.
+pub fn read(text: &str) -> Result<WindowList, FileError> {
+    let mut windows: WindowList = HashMap::new();
+    let mut session: Option<Session> = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let error = |message: &str| FileError::new(FileKind::Snapshot, i + 1, message);
+        let number = |field: &str| {
+            field.parse().map_err(|_| error(&format!("invalid number {:?}", field)))
+        };
+        let fields: Vec<String> = line.split('\t').map(unescape).collect();
+        let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+
+        if i == 0 {
+            match fields.as_slice() {
+                [HEADER, VERSION] => continue,
+                [HEADER, version] => {
+                    return Err(error(&format!("unsupported version {:?}", version)))
+                }
+                _ => return Err(error("not a tinfo snapshot")),
+            }
+        }
+
+        match fields.as_slice() {
+            [""] => {}
+            ["session", name] => {
+                let new = Session::new("", name);
+                windows.insert(new.clone(), Window::new(vec![], false));
+                session = Some(new);
+            }
+            ["window", index, name, width, height, layout, active] => {
+                let window = session.as_ref()
+                    .and_then(|session| windows.get_mut(session))
+                    .ok_or_else(|| error("window outside of any session"))?;
+                let mut tab = Tab::new(name, number(index)?);
+                tab.width = number(width)?;
+                tab.height = number(height)?;
+                tab.layout = layout.to_string();
+                tab.flags.active = *active == "1";
+                window.push(tab);
+            }
+            ["pane", index, active, path, command] => {
+                let tab = session.as_ref()
+                    .and_then(|session| windows.get_mut(session))
+                    .and_then(|window| window.tabs.last_mut())
+                    .ok_or_else(|| error("pane outside of any window"))?;
+                tab.panes.push(Pane {
+                    index: number(index)?,
+                    active: *active == "1",
+                    path: path.to_string(),
+                    command: command.to_string(),
+                    ..Pane::default()
+                });
+            }
+            [kind, ..] => return Err(error(&format!("unexpected {:?} line", kind))),
+            [] => {}
+        }
+    }
+
+    if windows.values().any(|window| window.tabs.iter().any(|tab| tab.panes.is_empty())) {
+        return Err(FileError::new(FileKind::Snapshot, text.lines().count(), "window without any panes"));
+    }
+    Ok(windows)
+}
+
+/// Whether `command` runs an interactive shell, which a restored pane will
+/// be running anyway.
+fn is_shell(command: &str) -> bool {
+    let login = env::var("SHELL").unwrap_or_default();
+    let login = Path::new(&login).file_name().and_then(|name| name.to_str());
+    let program = command.split_whitespace().next().unwrap_or_default();
+    let program = Path::new(program).file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    let command = program.trim_start_matches('-');
+    login == Some(command)
+        || ["sh", "bash", "zsh", "fish", "dash", "ksh", "mksh", "tcsh", "csh"].contains(&command)
+}
+
+fn restore_session(
+    tmux: &dyn TmuxBackend,
+    server: &Server,
+    session: &Session,
+    window: &Window,
+) -> Result<(), Error> {
+    let mut id = None;
+    let mut active_window = None;
+
+    for tab in window.tabs.iter() {
+        let mut panes: Vec<&Pane> = tab.panes.iter().collect();
+        panes.sort_by_key(|pane| pane.index);
+        let (&first, rest) = match panes.split_first() {
+            Some(split) => split,
+            None => continue,
+        };
+        let index = tab.number.to_string();
+
+        let created = match id {
+            None => {
+                let (width, height) = (tab.width.to_string(), tab.height.to_string());
+                let created = create(tmux, server, &[
+                    "new-session", "-d", "-P", "-s", &session.name, "-n", &tab.name,
+                    "-x", &width, "-y", &height, "-c", &first.path,
+                ])?;
+                if created.window_index != tab.number {
+                    let from = format!("{}:{}", created.session_id, created.window_index);
+                    let to = format!("{}:{}", created.session_id, index);
+                    tmux.run(server, &["move-window", "-s", &from, "-t", &to])?;
+                }
+                id = Some(created.session_id.clone());
+                created
+            }
+            Some(ref id) => {
+                let target = format!("{}:{}", id, index);
+                create(tmux, server, &[
+                    "new-window", "-d", "-P", "-t", &target, "-n", &tab.name, "-c", &first.path,
+                ])?
+            }
+        };
+        let target = format!("{}:{}", created.session_id, index);
+
+        // tmux hands a layout's cells to panes in index order, and puts a new
+        // pane straight after the one split, so always split the newest.
+        let mut panes = vec![(first, created.pane.id)];
+        for pane in rest {
+            let last = &panes[panes.len() - 1].1;
+            let split = create(tmux, server, &["split-window", "-d", "-P", "-t", last, "-c", &pane.path])?;
+            panes.push((pane, split.pane.id));
+        }
+        if !tab.layout.is_empty() {
+            tmux.run(server, &["select-layout", "-t", &target, &tab.layout])?;
+        }
+        for (pane, id) in panes.iter() {
+            if !pane.command.is_empty() && !is_shell(&pane.command) {
+                type_command(tmux, server, id, &pane.command)?;
+            }
+            if pane.active {
+                tmux.run(server, &["select-pane", "-t", id])?;
+            }
+        }
+        if tab.flags.active {
+            active_window = Some(target);
+        }
+    }
+
+    if let Some(target) = active_window {
+        tmux.run(server, &["select-window", "-t", &target])?;
+    }
+    Ok(())
+}
+
+/// Recreate every session in `snapshot` on `server`, skipping any which
+/// already exist there. Returns the names of those that were skipped.
+pub fn restore(tmux: &dyn TmuxBackend, server: &Server, snapshot: &WindowList) -> Result<Vec<String>, Error> {
+    let existing: Vec<String> = match build_windowlist(tmux, server) {
+        Ok(windows) => windows.keys().map(|session| session.name.clone()).collect(),
+        Err(Error::NoServer(_)) => vec![],
+        Err(e) => return Err(e),
+    };
+
+    let mut skipped = vec![];
+    for (session, window) in Listing::new(snapshot, &Sort::default()).0 {
+        if existing.contains(&session.name) {
+            skipped.push(session.name.clone());
+            continue;
+        }
+        restore_session(tmux, server, session, window)?;
+    }
+    Ok(skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{PaneRecord, Record};
+    use crate::testing::{calls_since, created};
+    use crate::tmux::FakeBackend;
+
+    const SNAPSHOT: &str = "tinfo-snapshot\t1
+session\twork\\there
+window\t1\tvim\t80\t24\tlayout\t1
+pane\t0\t0\t/src\tzsh
+pane\t1\t1\t/src\tcargo watch -x test
+window\t3\tlogs\t80\t24\t\t0
+pane\t0\t1\t/var/log\ttail
+";
+
+    #[test]
+    fn test_escaping() {
+        for field in ["plain", "tab\there", "new\nline", "back\\slash\\t", "\\"].iter() {
+            assert_eq!(unescape(&escape(field)), *field);
+        }
+    }
+
+    #[test]
+    fn test_foreground_pid() {
+        let stat = "4242 (tail (1) x) S 4241 4242 4241 34818 4250 4194304 120 0 0 0";
+        assert_eq!(foreground_pid(stat), Some(4250));
+        assert_eq!(foreground_pid("1 (init) S 0 1 1 0 -1 4194560"), None);
+        assert_eq!(foreground_pid("garbage"), None);
+    }
+
+    #[test]
+    fn test_quote_cmdline() {
+        assert_eq!(quote_cmdline(b"tail\0-f\0/etc/hostname\0").as_deref(), Some("tail -f /etc/hostname"));
+        assert_eq!(quote_cmdline(b"vim\0my notes.txt\0").as_deref(), Some("vim 'my notes.txt'"));
+        assert_eq!(quote_cmdline(b""), None);
+    }
+
+    #[test]
+    fn test_is_shell() {
+        assert!(is_shell("-bash"));
+        assert!(is_shell("/bin/zsh -l"));
+        assert!(!is_shell("tail -f /var/log/syslog"));
+        assert!(!is_shell(""));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let windows = read(SNAPSHOT).unwrap();
+        let mut out = vec![];
+        write(&mut out, &windows).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), SNAPSHOT);
+
+        let work = &windows[&Session::new("", "work\there")];
+        assert_eq!(work.tabs.len(), 2);
+        assert_eq!(work.tabs[0].panes[1].command, "cargo watch -x test");
+    }
+
+    #[test]
+    fn test_read_errors() {
+        assert_eq!(
+            read("tinfo-snapshot\t2\n").unwrap_err().message,
+            "unsupported version \"2\""
+        );
+        assert_eq!(read("session\twork\n").unwrap_err().message, "not a tinfo snapshot");
+        let err = read("tinfo-snapshot\t1\nsession\twork\nwindow\tone\tvim\t80\t24\t\t1\n").unwrap_err();
+        assert_eq!(err, FileError::new(FileKind::Snapshot, 3, "invalid number \"one\""));
+        let err = read("tinfo-snapshot\t1\npane\t0\t1\t/\tzsh\n").unwrap_err();
+        assert_eq!(err.message, "pane outside of any window");
+    }
+
+    #[test]
+    fn test_restore() {
+        let tmux = FakeBackend::new()
+            .fail("list-sessions", "no server running on /tmp/tmux-1000/default")
+            .respond("new-session", &created("$4", "0", "%1"))
+            .respond("new-window", &created("$4", "3", "%2"))
+            .respond("split-window", &created("$4", "1", "%3"));
+        let snapshot = read(SNAPSHOT).unwrap();
+        let skipped = restore(&tmux, &Server::Default, &snapshot).unwrap();
+        assert!(skipped.is_empty());
+
+        let calls = calls_since(&tmux, 1);
+        let format = PaneRecord::format();
+        assert_eq!(calls, vec![
+            format!("new-session -F {} -d -P -s work\there -n vim -x 80 -y 24 -c /src", format),
+            "move-window -s $4:0 -t $4:1".to_string(),
+            format!("split-window -F {} -d -P -t %1 -c /src", format),
+            "select-layout -t $4:1 layout".to_string(),
+            "send-keys -t %3 -l cargo watch -x test ; send-keys -t %3 Enter".to_string(),
+            "select-pane -t %3".to_string(),
+            format!("new-window -F {} -d -P -t $4:3 -n logs -c /var/log", format),
+            "send-keys -t %2 -l tail ; send-keys -t %2 Enter".to_string(),
+            "select-pane -t %2".to_string(),
+            "select-window -t $4:1".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn test_restore_pane_order() {
+        let tmux = FakeBackend::new()
+            .fail("list-sessions", "no server running on /tmp/tmux-1000/default")
+            .respond("new-session", &created("$4", "0", "%1"))
+            .respond("split-window", &created("$4", "0", "%2"));
+        let snapshot = read(concat!(
+            "tinfo-snapshot\t1\n",
+            "session\twork\n",
+            "window\t0\tvim\t80\t24\t\t1\n",
+            "pane\t1\t0\t/b\tzsh\n",
+            "pane\t0\t1\t/a\tzsh\n",
+        )).unwrap();
+        restore(&tmux, &Server::Default, &snapshot).unwrap();
+
+        let calls = calls_since(&tmux, 1);
+        let format = PaneRecord::format();
+        assert_eq!(calls[..2], [
+            format!("new-session -F {} -d -P -s work -n vim -x 80 -y 24 -c /a", format),
+            format!("split-window -F {} -d -P -t %1 -c /b", format),
+        ]);
+        assert!(calls.contains(&"select-pane -t %1".to_string()));
+    }
+}
@@ -0,0 +1,148 @@
+//! The order sessions are listed in.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Session, Window};
+
+/// What sessions are sorted by. Whatever the key, ties fall back to the
+/// session's name, then its server and id, and windows always come out in
+/// index order within their session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    /// Alphabetically by name.
+    #[default]
+    Name,
+    /// Most recently used first.
+    Activity,
+    /// Oldest first.
+    Created,
+    /// Most panes first.
+    Panes,
+    /// Attached sessions first.
+    Attached,
+}
+
+impl SortKey {
+    pub const NAMES: &'static [&'static str] = &["name", "activity", "created", "panes", "attached"];
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UnknownSortKey(String);
+
+impl fmt::Display for UnknownSortKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown sort key {:?}, expected one of: {}",
+            self.0,
+            SortKey::NAMES.join(", ")
+        )
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = UnknownSortKey;
+
+    fn from_str(s: &str) -> Result<SortKey, UnknownSortKey> {
+        match s {
+            "name" => Ok(SortKey::Name),
+            "activity" => Ok(SortKey::Activity),
+            "created" => Ok(SortKey::Created),
+            "panes" => Ok(SortKey::Panes),
+            "attached" => Ok(SortKey::Attached),
+            _ => Err(UnknownSortKey(s.to_string())),
+        }
+    }
+}
+
+/// A sort key, and whether to turn the whole order around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sort {
+    pub key: SortKey,
+    pub reverse: bool,
+}
+
+fn pane_count(window: &Window) -> usize {
+    window.tabs.iter().map(|tab| tab.panes.len()).sum()
+}
+
+/// tmux's `$id`s are numbered, and should sort that way.
+fn id_number(session: &Session) -> Option<usize> {
+    session.id.trim_start_matches('$').parse().ok()
+}
+
+impl Sort {
+    pub fn compare(&self, a: (&Session, &Window), b: (&Session, &Window)) -> Ordering {
+        let ((a, a_window), (b, b_window)) = (a, b);
+        let by_key = match self.key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Activity => b_window.activity.cmp(&a_window.activity),
+            SortKey::Created => a_window.created.cmp(&b_window.created),
+            SortKey::Panes => pane_count(b_window).cmp(&pane_count(a_window)),
+            SortKey::Attached => b_window.attached.cmp(&a_window.attached),
+        };
+        let ordering = by_key
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| a.server.cmp(&b.server))
+            .then_with(|| id_number(a).cmp(&id_number(b)))
+            .then_with(|| a.id.cmp(&b.id));
+        if self.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Tab;
+
+    fn window(attached: bool, created: u64, activity: u64, panes: usize) -> Window {
+        let mut tab = Tab::new("zsh", 0);
+        tab.panes = vec![crate::Pane::default(); panes];
+        let mut window = Window::new(vec![tab], attached);
+        window.created = created;
+        window.activity = activity;
+        window
+    }
+
+    fn sorted(sort: Sort) -> Vec<String> {
+        let sessions = [
+            (Session::new("$10", "b"), window(false, 100, 500, 1)),
+            (Session::new("$2", "b"), window(true, 300, 400, 3)),
+            (Session::new("$1", "a"), window(false, 200, 600, 2)),
+        ];
+        let mut rows: Vec<_> = sessions.iter().map(|(s, w)| (s, w)).collect();
+        rows.sort_by(|a, b| sort.compare(*a, *b));
+        rows.iter().map(|(s, _)| s.id.clone()).collect()
+    }
+
+    #[test]
+    fn test_keys() {
+        let by = |key| sorted(Sort { key, reverse: false });
+        assert_eq!(by(SortKey::Name), vec!["$1", "$2", "$10"]);
+        assert_eq!(by(SortKey::Activity), vec!["$1", "$10", "$2"]);
+        assert_eq!(by(SortKey::Created), vec!["$10", "$1", "$2"]);
+        assert_eq!(by(SortKey::Panes), vec!["$2", "$1", "$10"]);
+        assert_eq!(by(SortKey::Attached), vec!["$2", "$1", "$10"]);
+    }
+
+    #[test]
+    fn test_reverse() {
+        let sort = Sort { key: SortKey::Name, reverse: true };
+        assert_eq!(sorted(sort), vec!["$10", "$2", "$1"]);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("activity".parse(), Ok(SortKey::Activity));
+        assert_eq!(
+            "size".parse::<SortKey>(),
+            Err(UnknownSortKey("size".to_string()))
+        );
+    }
+}
@@ -0,0 +1,407 @@
+//! Output templates, in the spirit of tmux's `-F` formats.
+//!
+//! A template is literal text with placeholders in braces:
+//!
+//! - `{window.name}` expands to a variable.
+//! - `{window.name:20}` pads it to 20 columns, aligned left; `{panes:>3}`
+//!   aligns right instead.
+//! - `{window.activity:time}` shows a time, given in seconds since the
+//!   epoch, as a UTC date and time.
+//! - `{?attached:yes}` and `{?attached:yes:no}` expand to one template or
+//!   the other, depending on whether the variable is set (non-empty, and not
+//!   `0`). Either branch may contain placeholders of its own.
+//!
+//! A backslash escapes the next character, so `\{`, `\}`, `\:` and `\\`
+//! stand for themselves.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use failure::Fail;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{Pane, Session, Tab, Window};
+
+/// Every variable a template can refer to.
+pub const VARIABLES: &[&str] = &[
+    "server",
+    "session",
+    "session.id",
+    "session.created",
+    "session.activity",
+    "session.last_attached",
+    "attached",
+    "window.index",
+    "window.name",
+    "window.layout",
+    "window.activity",
+    "window.flags",
+    "window.active",
+    "window.last",
+    "window.zoomed",
+    "window.bell",
+    "window.silence",
+    "panes",
+    "width",
+    "height",
+    "pane.index",
+    "pane.id",
+    "pane.active",
+    "pane.pid",
+    "pane.width",
+    "pane.height",
+    "pane.command",
+    "pane.path",
+    "pane.title",
+];
+
+#[derive(Debug, PartialEq)]
+pub enum TemplateError {
+    UnknownVariable(String),
+    BadWidth(String),
+    Unclosed,
+    UnexpectedBrace,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::UnknownVariable(name) => write!(
+                f,
+                "unknown variable {:?} in format, expected one of: {}",
+                name,
+                VARIABLES.join(", ")
+            ),
+            TemplateError::BadWidth(spec) => write!(f, "invalid width {:?} in format", spec),
+            TemplateError::Unclosed => write!(f, "unclosed `{{` in format"),
+            TemplateError::UnexpectedBrace => write!(f, "unexpected `}}` in format"),
+        }
+    }
+}
+
+impl Fail for TemplateError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Text(String),
+    Variable {
+        name: String,
+        align: Align,
+        width: usize,
+        time: bool,
+    },
+    Conditional {
+        name: String,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+/// A parsed template, ready to render.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template(Vec<Node>);
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Template, TemplateError> {
+        let mut chars = source.chars().peekable();
+        let (nodes, stop) = parse_nodes(&mut chars, false)?;
+        match stop {
+            None => Ok(Template(nodes)),
+            Some(_) => Err(TemplateError::UnexpectedBrace),
+        }
+    }
+
+    /// Expand the template against `row`.
+    pub fn render(&self, row: &Row) -> String {
+        let mut out = String::new();
+        render_nodes(&self.0, row, &mut out);
+        out
+    }
+}
+
+/// Parse up to the end of input or, inside a conditional, up to the `:` or
+/// `}` that ends the current branch, which is returned alongside.
+fn parse_nodes(
+    chars: &mut Peekable<Chars>,
+    in_branch: bool,
+) -> Result<(Vec<Node>, Option<char>), TemplateError> {
+    let mut nodes = vec![];
+    let mut text = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.extend(chars.next()),
+            '{' => {
+                if !text.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text)));
+                }
+                nodes.push(parse_placeholder(chars)?);
+            }
+            ':' | '}' if in_branch => {
+                if !text.is_empty() {
+                    nodes.push(Node::Text(text));
+                }
+                return Ok((nodes, Some(c)));
+            }
+            '}' => return Err(TemplateError::UnexpectedBrace),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        nodes.push(Node::Text(text));
+    }
+    if in_branch {
+        return Err(TemplateError::Unclosed);
+    }
+    Ok((nodes, None))
+}
+
+/// Parse a placeholder, having just consumed its opening brace.
+fn parse_placeholder(chars: &mut Peekable<Chars>) -> Result<Node, TemplateError> {
+    let conditional = chars.peek() == Some(&'?');
+    if conditional {
+        chars.next();
+    }
+
+    let mut name = String::new();
+    let stop = loop {
+        match chars.next() {
+            Some(c @ (':' | '}')) => break c,
+            Some(c) => name.push(c),
+            None => return Err(TemplateError::Unclosed),
+        }
+    };
+    if !VARIABLES.contains(&name.as_str()) {
+        return Err(TemplateError::UnknownVariable(name));
+    }
+
+    if conditional {
+        let (then, stop) = match stop {
+            ':' => parse_nodes(chars, true)?,
+            _ => (vec![], Some('}')),
+        };
+        let otherwise = match stop {
+            Some(':') => match parse_nodes(chars, true)? {
+                (nodes, Some('}')) => nodes,
+                _ => return Err(TemplateError::UnexpectedBrace),
+            },
+            _ => vec![],
+        };
+        return Ok(Node::Conditional { name, then, otherwise });
+    }
+
+    if stop == '}' {
+        return Ok(Node::Variable { name, align: Align::Left, width: 0, time: false });
+    }
+    let mut spec = String::new();
+    loop {
+        match chars.next() {
+            Some('}') => break,
+            Some(c) => spec.push(c),
+            None => return Err(TemplateError::Unclosed),
+        }
+    }
+    if spec == "time" {
+        return Ok(Node::Variable { name, align: Align::Left, width: 0, time: true });
+    }
+    let (align, digits) = match spec.chars().next() {
+        Some('>') => (Align::Right, &spec[1..]),
+        Some('<') => (Align::Left, &spec[1..]),
+        _ => (Align::Left, &spec[..]),
+    };
+    let width = digits.parse().map_err(|_| TemplateError::BadWidth(spec.clone()))?;
+    Ok(Node::Variable { name, align, width, time: false })
+}
+
+fn render_nodes(nodes: &[Node], row: &Row, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Variable { name, align, width, time } => {
+                let mut value = row.get(name);
+                if *time {
+                    value = match value.parse() {
+                        Ok(0) | Err(_) => String::new(),
+                        Ok(secs) => format_time(secs),
+                    };
+                }
+                let padding = " ".repeat(width.saturating_sub(value.width()));
+                match align {
+                    Align::Left => {
+                        out.push_str(&value);
+                        out.push_str(&padding);
+                    }
+                    Align::Right => {
+                        out.push_str(&padding);
+                        out.push_str(&value);
+                    }
+                }
+            }
+            Node::Conditional { name, then, otherwise } => {
+                let value = row.get(name);
+                if value.is_empty() || value == "0" {
+                    render_nodes(otherwise, row, out);
+                } else {
+                    render_nodes(then, row, out);
+                }
+            }
+        }
+    }
+}
+
+/// Format seconds since the epoch as an ISO 8601 UTC date and time.
+fn format_time(secs: u64) -> String {
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Howard Hinnant's days_from_civil, run backwards.
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// The things a single line of output can describe: always a session, and
+/// maybe one of its windows and one of that window's panes.
+pub struct Row<'a> {
+    pub session: &'a Session,
+    pub window: &'a Window,
+    pub tab: Option<&'a Tab>,
+    pub pane: Option<&'a Pane>,
+}
+
+fn flag(value: bool) -> String {
+    if value { "1" } else { "0" }.to_string()
+}
+
+impl<'a> Row<'a> {
+    /// The value of a variable, or nothing if this row doesn't have it.
+    pub fn get(&self, name: &str) -> String {
+        let tab = self.tab;
+        let pane = self.pane;
+        let value = match name {
+            "server" => Some(self.session.server.to_string()),
+            "session" => Some(self.session.name.clone()),
+            "session.id" => Some(self.session.id.clone()),
+            "session.created" => Some(self.window.created.to_string()),
+            "session.activity" => Some(self.window.activity.to_string()),
+            "session.last_attached" => self.window.last_attached.map(|t| t.to_string()),
+            "attached" => Some(flag(self.window.attached)),
+            "window.index" => tab.map(|t| t.number.to_string()),
+            "window.name" => tab.map(|t| t.name.clone()),
+            "window.layout" => tab.map(|t| t.layout.clone()),
+            "window.activity" => tab.map(|t| t.activity.to_string()),
+            "window.flags" => tab.map(|t| t.flags.to_string()),
+            "window.active" => tab.map(|t| flag(t.flags.active)),
+            "window.last" => tab.map(|t| flag(t.flags.last)),
+            "window.zoomed" => tab.map(|t| flag(t.flags.zoomed)),
+            "window.bell" => tab.map(|t| flag(t.flags.bell)),
+            "window.silence" => tab.map(|t| flag(t.flags.silence)),
+            "panes" => tab.map(|t| t.panes.len().to_string()),
+            "width" => tab.map(|t| t.width.to_string()),
+            "height" => tab.map(|t| t.height.to_string()),
+            "pane.index" => pane.map(|p| p.index.to_string()),
+            "pane.id" => pane.map(|p| p.id.clone()),
+            "pane.active" => pane.map(|p| flag(p.active)),
+            "pane.pid" => pane.map(|p| p.pid.to_string()),
+            "pane.width" => pane.map(|p| p.width.to_string()),
+            "pane.height" => pane.map(|p| p.height.to_string()),
+            "pane.command" => pane.map(|p| p.command.clone()),
+            "pane.path" => pane.map(|p| p.path.clone()),
+            "pane.title" => pane.map(|p| p.title.clone()),
+            _ => None,
+        };
+        value.unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(source: &str) -> String {
+        let session = Session::new("$1", "work");
+        let mut tab = Tab::new("vim", 2);
+        tab.width = 80;
+        tab.height = 24;
+        tab.activity = 1792026973;
+        tab.flags.last = true;
+        let window = Window::new(vec![tab.clone()], true);
+        let row = Row { session: &session, window: &window, tab: Some(&tab), pane: None };
+        Template::parse(source).unwrap().render(&row)
+    }
+
+    #[test]
+    fn test_variables() {
+        assert_eq!(render("{session}:{window.index} {window.name}"), "work:2 vim");
+        assert_eq!(render("[{width}x{height}] {panes}"), "[80x24] 0");
+        assert_eq!(render("{pane.command}"), "");
+        assert_eq!(render("{window.flags} {?window.last:last}"), "- last");
+    }
+
+    #[test]
+    fn test_times() {
+        assert_eq!(render("{window.activity:time}"), "2026-10-15T01:16:13Z");
+        assert_eq!(render("{session.created:time}|{?session.last_attached:never:x}"), "|x");
+        assert_eq!(format_time(951825600), "2000-02-29T12:00:00Z");
+        assert_eq!(format_time(1), "1970-01-01T00:00:01Z");
+    }
+
+    #[test]
+    fn test_padding() {
+        assert_eq!(render("{window.name:6}|"), "vim   |");
+        assert_eq!(render("{window.name:<6}|"), "vim   |");
+        assert_eq!(render("{window.index:>3}|"), "  2|");
+        assert_eq!(render("{window.name:2}|"), "vim|");
+    }
+
+    #[test]
+    fn test_conditionals() {
+        assert_eq!(render("{?attached:*}"), "*");
+        assert_eq!(render("{?attached: (attached):}"), " (attached)");
+        assert_eq!(render("{?panes:has panes:no panes}"), "no panes");
+        assert_eq!(render("{?pane.id:{pane.id}:{window.name:>4}}"), " vim");
+        assert_eq!(render("{?attached}"), "");
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert_eq!(render("\\{session\\}"), "{session}");
+        assert_eq!(render("{?attached:a\\:b}"), "a:b");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Template::parse("{nope}"),
+            Err(TemplateError::UnknownVariable("nope".to_string()))
+        );
+        assert_eq!(Template::parse("{session"), Err(TemplateError::Unclosed));
+        assert_eq!(Template::parse("{?attached:yes"), Err(TemplateError::Unclosed));
+        assert_eq!(Template::parse("session}"), Err(TemplateError::UnexpectedBrace));
+        assert_eq!(
+            Template::parse("{session:wide}"),
+            Err(TemplateError::BadWidth("wide".to_string()))
+        );
+    }
+}
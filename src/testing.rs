@@ -0,0 +1,50 @@
+//! Canned tmux output, and ways of checking what was run, for tests
+//! against `FakeBackend`, here and in the binary, which gets them through
+//! the `test-support` feature.
+
+use crate::format::SEPARATOR;
+use crate::tmux::FakeBackend;
+
+/// A line of `-F` output, its fields separated as tmux would.
+pub fn line(fields: &[&str]) -> String {
+    let mut line = fields.join(&SEPARATOR.to_string());
+    line.push('\n');
+    line
+}
+
+/// What tmux prints when asked for the `PaneRecord` of a pane it's just
+/// made with `-P`: pane `pane`, in window `window` of session `id`.
+pub fn created(id: &str, window: &str, pane: &str) -> String {
+    line(&[id, window, "0", pane, "1", "1", "80", "24", "zsh", "zsh", "/"])
+}
+
+/// The commands `tmux` has been asked to run, after the first `before`,
+/// each as a line.
+pub fn calls_since(tmux: &FakeBackend, before: usize) -> Vec<String> {
+    tmux.calls()[before..].iter().map(|call| call.join(" ")).collect()
+}
+
+/// A server with two sessions: `work`, attached, with a shell in window 0
+/// and vim beside a cargo build in window 1; and `api: dev ü`, tailing logs
+/// in window 3.
+pub fn server() -> FakeBackend {
+    let sessions = [
+        line(&["$0", "work", "2", "1", "1000", "3000", "3000"]),
+        line(&["$1", "api: dev ü", "1", "0", "2000", "2500", ""]),
+    ].concat();
+    let windows = [
+        line(&["$0", "0", "80", "24", "c1d2,80x24,0,0,0", "2900", "0", "1", "0", "0", "0", "zsh"]),
+        line(&["$0", "1", "80", "24", "a3f1,80x24,0,0[80x12,0,0,1,80x11,0,13,2]", "3000", "1", "0", "0", "0", "0", "vim main.rs"]),
+        line(&["$1", "3", "80", "24", "c1d4,80x24,0,0,3", "2500", "1", "0", "0", "1", "0", "logs"]),
+    ].concat();
+    let panes = [
+        line(&["$0", "0", "0", "%0", "1", "100", "80", "24", "zsh", "zsh", "/home/me"]),
+        line(&["$0", "1", "0", "%1", "1", "101", "40", "24", "vim", "vim", "/src/tinfo"]),
+        line(&["$0", "1", "1", "%2", "0", "102", "39", "24", "cargo", "build", "/src/tinfo"]),
+        line(&["$1", "3", "0", "%3", "1", "103", "80", "24", "tail", "tail", "/var/log"]),
+    ].concat();
+    FakeBackend::new()
+        .respond("list-sessions", &sessions)
+        .respond("list-windows", &windows)
+        .respond("list-panes", &panes)
+}
@@ -0,0 +1,305 @@
+//! The boundary between tinfo and tmux itself.
+//!
+//! Everything that talks to tmux goes through a `TmuxBackend`, so that the
+//! rest of tinfo can be exercised against canned output instead of a live
+//! server.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::error::Error;
+
+pub trait TmuxBackend {
+    /// Run a tmux command against `server` to completion and return its
+    /// standard output.
+    fn output(&self, server: &Server, args: &[&str]) -> Result<String, Error>;
+
+    /// Run a tmux command against `server` to completion, for its effect
+    /// alone.
+    fn run(&self, server: &Server, args: &[&str]) -> Result<(), Error> {
+        self.output(server, args).map(|_| ())
+    }
+
+    /// Hand the terminal over to a tmux command against `server`, replacing
+    /// this process with it. Only returns if that couldn't happen.
+    fn exec(&self, server: &Server, args: &[&str]) -> Result<(), Error>;
+}
+
+/// Which tmux server to talk to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Server {
+    /// Whichever server tmux would pick on its own.
+    #[default]
+    Default,
+    /// A named socket in tmux's socket directory, as with `tmux -L`.
+    Name(String),
+    /// A socket at an explicit path, as with `tmux -S`.
+    Path(PathBuf),
+}
+
+impl Server {
+    /// The server we're running inside of, if any, according to `$TMUX`.
+    pub fn from_env() -> Server {
+        match env::var("TMUX") {
+            Ok(value) => Server::from_tmux_var(&value),
+            Err(_) => Server::Default,
+        }
+    }
+
+    /// Parse the value of `$TMUX`, which looks like `socket,pid,session`.
+    pub fn from_tmux_var(value: &str) -> Server {
+        match value.split(',').next() {
+            Some(path) if !path.is_empty() => Server::Path(PathBuf::from(path)),
+            _ => Server::Default,
+        }
+    }
+
+    /// tmux's socket directory: `$TMUX_TMPDIR/tmux-$UID`, or
+    /// `/tmp/tmux-$UID`.
+    fn socket_dir() -> PathBuf {
+        let tmpdir = env::var_os("TMUX_TMPDIR").unwrap_or_else(|| "/tmp".into());
+        let uid = unsafe { libc::getuid() };
+        Path::new(&tmpdir).join(format!("tmux-{}", uid))
+    }
+
+    /// Every server socket belonging to the current user, found in tmux's
+    /// socket directory.
+    pub fn discover() -> io::Result<Vec<Server>> {
+        Server::discover_in(&Server::socket_dir())
+    }
+
+    /// Every socket in `dir`. tmux only makes the directory when it first
+    /// starts a server, so until then there are none.
+    fn discover_in(dir: &Path) -> io::Result<Vec<Server>> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+        let mut sockets = vec![];
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_socket() {
+                sockets.push(entry.path());
+            }
+        }
+        sockets.sort();
+        Ok(sockets.into_iter().map(Server::Path).collect())
+    }
+
+    /// The path of the server's socket.
+    pub fn socket(&self) -> PathBuf {
+        match self {
+            Server::Default => Server::socket_dir().join("default"),
+            Server::Name(name) => Server::socket_dir().join(name),
+            Server::Path(path) => path.clone(),
+        }
+    }
+
+    /// Whether this is the same server as `other`, however each of them was
+    /// named.
+    pub fn is_same(&self, other: &Server) -> bool {
+        self == other || self.socket() == other.socket()
+    }
+
+    /// The arguments which point tmux at this server.
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            Server::Default => vec![],
+            Server::Name(name) => vec!["-L".to_string(), name.clone()],
+            Server::Path(path) => vec!["-S".to_string(), path.display().to_string()],
+        }
+    }
+}
+
+impl fmt::Display for Server {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Server::Default => write!(f, "default"),
+            Server::Name(name) => write!(f, "{}", name),
+            Server::Path(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// The whole command line which runs `args` against `server`.
+pub fn command_line(server: &Server, args: &[&str]) -> Vec<String> {
+    // Without -u, tmux decides from the locale whether we can cope with
+    // UTF-8, and if not replaces it (and our field separator) with `_`.
+    let mut line = vec!["tmux".to_string(), "-u".to_string()];
+    line.extend(server.args());
+    line.extend(args.iter().map(|arg| arg.to_string()));
+    line
+}
+
+/// Runs commands against the real tmux binary.
+#[derive(Debug, Default)]
+pub struct ProcessBackend;
+
+impl ProcessBackend {
+    fn command(&self, server: &Server, args: &[&str]) -> process::Command {
+        let line = command_line(server, args);
+        let mut command = process::Command::new(&line[0]);
+        command.args(&line[1..]);
+        command
+    }
+}
+
+/// Work out what went wrong from a failed tmux's complaint.
+fn failure(server: &Server, stderr: &str) -> Error {
+    let stderr = stderr.trim();
+    if stderr.starts_with("no server running") || stderr.starts_with("error connecting to") {
+        Error::NoServer(server.clone())
+    } else {
+        Error::Tmux(stderr.to_string())
+    }
+}
+
+fn spawn_error(e: io::Error) -> Error {
+    if e.kind() == io::ErrorKind::NotFound {
+        Error::TmuxNotInstalled
+    } else {
+        Error::Io(e)
+    }
+}
+
+impl TmuxBackend for ProcessBackend {
+    fn output(&self, server: &Server, args: &[&str]) -> Result<String, Error> {
+        let out = self.command(server, args).output().map_err(spawn_error)?;
+        if !out.status.success() {
+            return Err(failure(server, &String::from_utf8_lossy(&out.stderr)));
+        }
+        Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+    }
+
+    fn exec(&self, server: &Server, args: &[&str]) -> Result<(), Error> {
+        Err(spawn_error(self.command(server, args).exec()))
+    }
+}
+
+/// An in-memory tmux which answers with canned output and records every
+/// command it is asked to run.
+#[derive(Debug, Default)]
+pub struct FakeBackend {
+    responses: HashMap<String, String>,
+    failures: HashMap<String, String>,
+    calls: RefCell<Vec<Vec<String>>>,
+}
+
+impl FakeBackend {
+    pub fn new() -> FakeBackend {
+        FakeBackend::default()
+    }
+
+    /// Answer every invocation of `command` (eg, `list-sessions`) with
+    /// `output`. Commands without a response print nothing.
+    pub fn respond(mut self, command: &str, output: &str) -> FakeBackend {
+        self.responses.insert(command.to_string(), output.to_string());
+        self
+    }
+
+    /// Fail every invocation of `command`, complaining with `stderr` as
+    /// tmux would.
+    pub fn fail(mut self, command: &str, stderr: &str) -> FakeBackend {
+        self.failures.insert(command.to_string(), stderr.to_string());
+        self
+    }
+
+    /// Every command run so far, in order, including the arguments which
+    /// select its server.
+    pub fn calls(&self) -> Vec<Vec<String>> {
+        self.calls.borrow().clone()
+    }
+
+    fn record(&self, server: &Server, args: &[&str]) -> Result<String, Error> {
+        let mut call = server.args();
+        call.extend(args.iter().map(|arg| arg.to_string()));
+        self.calls.borrow_mut().push(call);
+
+        let command = args.first().cloned().unwrap_or_default();
+        if let Some(stderr) = self.failures.get(command) {
+            return Err(failure(server, stderr));
+        }
+        Ok(self.responses.get(command).cloned().unwrap_or_default())
+    }
+}
+
+impl TmuxBackend for FakeBackend {
+    fn output(&self, server: &Server, args: &[&str]) -> Result<String, Error> {
+        self.record(server, args)
+    }
+
+    fn exec(&self, server: &Server, args: &[&str]) -> Result<(), Error> {
+        self.record(server, args).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_from_tmux_var() {
+        assert_eq!(
+            Server::from_tmux_var("/tmp/tmux-1000/default,4242,0"),
+            Server::Path(PathBuf::from("/tmp/tmux-1000/default"))
+        );
+        assert_eq!(Server::from_tmux_var(""), Server::Default);
+    }
+
+    #[test]
+    fn test_failure() {
+        let server = Server::Name("work".to_string());
+        match failure(&server, "no server running on /tmp/tmux-1000/work\n") {
+            Error::NoServer(s) => assert_eq!(s, server),
+            e => panic!("expected NoServer, got {:?}", e),
+        }
+        match failure(&server, "error connecting to /tmp/tmux-1000/work (No such file or directory)\n") {
+            Error::NoServer(_) => {}
+            e => panic!("expected NoServer, got {:?}", e),
+        }
+        match failure(&server, "can't find window: 7\n") {
+            Error::Tmux(message) => assert_eq!(message, "can't find window: 7"),
+            e => panic!("expected Tmux, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_server_args() {
+        assert!(Server::Default.args().is_empty());
+        assert_eq!(Server::Name("work".to_string()).args(), vec!["-L", "work"]);
+        assert_eq!(
+            Server::Path(PathBuf::from("/tmp/sock")).args(),
+            vec!["-S", "/tmp/sock"]
+        );
+    }
+
+    #[test]
+    fn test_server_is_same() {
+        let work = Server::Name("work".to_string());
+        assert!(work.is_same(&Server::Path(Server::socket_dir().join("work"))));
+        assert!(Server::Default.is_same(&Server::Path(Server::socket_dir().join("default"))));
+        assert!(!work.is_same(&Server::Default));
+    }
+
+    #[test]
+    fn test_discover_in() {
+        let dir = env::temp_dir().join(format!("tinfo-discover-{}", std::process::id()));
+        assert_eq!(Server::discover_in(&dir).unwrap(), vec![]);
+
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("notes"), "").unwrap();
+        let _listener = std::os::unix::net::UnixListener::bind(dir.join("work")).unwrap();
+        let found = Server::discover_in(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(found.unwrap(), vec![Server::Path(dir.join("work"))]);
+    }
+}
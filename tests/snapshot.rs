@@ -0,0 +1,144 @@
+//! Save sessions from a real tmux server, then restore them onto a fresh one.
+
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use regex::Regex;
+use tinfo::snapshot;
+use tinfo::tmux::{ProcessBackend, Server};
+use tinfo::{build_windowlist, WindowList};
+
+/// A tmux server on a socket of its own, killed when dropped.
+struct Private(String);
+
+impl Private {
+    fn new() -> Private {
+        Private(format!("tinfo-test-{}", std::process::id()))
+    }
+
+    fn server(&self) -> Server {
+        Server::Name(self.0.clone())
+    }
+
+    fn tmux(&self, args: &[&str]) {
+        let status = Command::new("tmux")
+            .args(["-u", "-f", "/dev/null", "-L", &self.0])
+            .args(args)
+            .status()
+            .expect("couldn't run tmux");
+        assert!(status.success(), "tmux {:?} failed", args);
+    }
+
+    /// Kill the server, and wait for it to go: until it has, a new client
+    /// can connect to it on its way out instead of starting a new one.
+    fn kill(&self) {
+        let _ = Command::new("tmux")
+            .args(["-L", &self.0, "kill-server"])
+            .status();
+        let socket = self.server().socket();
+        for _ in 0..100 {
+            if !socket.exists() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+impl Drop for Private {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// A layout's geometry, without the checksum and pane ids which differ
+/// from one server to the next.
+fn geometry(layout: &str) -> String {
+    let cell = Regex::new(r"(\d+x\d+,\d+,\d+),\d+").unwrap();
+    cell.replace_all(&layout[layout.find(',').map_or(0, |i| i + 1)..], "$1").into_owned()
+}
+
+/// The command line running in a window's first pane, as a snapshot would
+/// save it.
+fn command(windows: &WindowList, session: &str, index: usize) -> String {
+    let (_, window) = windows.iter().find(|(s, _)| s.name == session).expect("session should exist");
+    let tab = window.tabs.iter().find(|tab| tab.number == index).expect("window should exist");
+    tab.panes[0].command.clone()
+}
+
+/// Everything a snapshot promises to bring back, in a comparable form.
+fn shape(windows: &WindowList) -> Vec<String> {
+    let mut sessions: Vec<_> = windows.iter().collect();
+    sessions.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+    let mut lines = vec![];
+    for (session, window) in sessions {
+        lines.push(format!("session {}", session.name));
+        for tab in window.tabs.iter() {
+            lines.push(format!(
+                "window {} {} {} active={}",
+                tab.number,
+                tab.name,
+                geometry(&tab.layout),
+                tab.flags.active
+            ));
+            for pane in tab.panes.iter() {
+                lines.push(format!("pane {} {} active={}", pane.index, pane.path, pane.active));
+            }
+        }
+    }
+    lines
+}
+
+#[test]
+fn test_round_trip() {
+    if Command::new("tmux").arg("-V").output().is_err() {
+        eprintln!("tmux isn't installed, skipping");
+        return;
+    }
+
+    // A shell quick to start, and without any startup files, to type the
+    // restored commands into.
+    std::env::set_var("SHELL", "/bin/sh");
+    let private = Private::new();
+    let server = private.server();
+    let tmux = ProcessBackend;
+
+    private.tmux(&["new-session", "-d", "-s", "work", "-n", "edit", "-x", "120", "-y", "40", "-c", "/tmp"]);
+    private.tmux(&["split-window", "-d", "-h", "-t", "work:0", "-c", "/"]);
+    private.tmux(&["split-window", "-d", "-v", "-t", "work:0.1", "-c", "/usr"]);
+    private.tmux(&["new-window", "-d", "-t", "work:4", "-n", "logs", "-c", "/var"]);
+    private.tmux(&["new-window", "-d", "-t", "work:5", "-n", "tail", "-c", "/", "exec tail -f /etc/hostname"]);
+    private.tmux(&["new-session", "-d", "-s", "play", "-n", "scratch", "-x", "80", "-y", "24", "-c", "/"]);
+
+    let saved = snapshot::save(&tmux, &server).unwrap();
+    let mut file = vec![];
+    snapshot::write(&mut file, &saved).unwrap();
+    assert_eq!(command(&saved, "work", 5), "tail -f /etc/hostname");
+
+    private.kill();
+    // Start the fresh server without any tmux.conf, which might number
+    // panes differently, before restoring onto it.
+    private.tmux(&["start-server", ";", "set", "-g", "exit-empty", "off"]);
+    let loaded = snapshot::read(&String::from_utf8(file).unwrap()).unwrap();
+    let skipped = snapshot::restore(&tmux, &server, &loaded).unwrap();
+    assert!(skipped.is_empty());
+
+    let restored = build_windowlist(&tmux, &server).unwrap();
+    assert_eq!(shape(&restored), shape(&saved));
+
+    // The command is typed back in, arguments and all.
+    let mut restarted = String::new();
+    for _ in 0..100 {
+        restarted = command(&snapshot::save(&tmux, &server).unwrap(), "work", 5);
+        if restarted.starts_with("tail") {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    assert_eq!(restarted, "tail -f /etc/hostname");
+
+    // Restoring again leaves what's already there alone.
+    let skipped = snapshot::restore(&tmux, &server, &loaded).unwrap();
+    assert_eq!(skipped, vec!["play", "work"]);
+}